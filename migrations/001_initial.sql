@@ -0,0 +1,86 @@
+-- Tables that existed before migrations were introduced.
+-- IF NOT EXISTS because the shared DB already has them.
+
+CREATE TABLE IF NOT EXISTS invocations_raw(
+    id SERIAL PRIMARY KEY,
+
+    status TEXT NOT NULL,
+    -- 'RUN'
+    -- 'STOPPED'
+
+    start_time TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    update_time TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- If status is 'STOPPED', termination time.
+    -- If status is 'RUN', time of the next planned update.
+    -- So if status == 'RUN' and update_time < NOW(),
+    -- we can conclude that the invocation terminated abnormally
+    -- without updating the DB.
+    -- Let's call it 'LOST' (even though such status will never appear
+    -- in the DB explicitly).
+
+    data JSONB NOT NULL
+);
+
+CREATE OR REPLACE VIEW invocations AS
+SELECT
+    id,
+    CASE
+        WHEN status = 'RUN' AND update_time < NOW() THEN 'LOST'
+        ELSE status
+    END AS status,
+    start_time,
+    update_time,
+    data
+FROM invocations_raw;
+
+CREATE TABLE IF NOT EXISTS solutions(
+    id SERIAL PRIMARY KEY,
+    problem_id INTEGER NOT NULL,  -- could be foreign key, but maybe we don't want to bother with the "problems" table
+
+    data TEXT NOT NULL,  -- move instructions as text
+    moves_cost BIGINT NOT NULL,
+    image_distance BIGINT NOT NULL,
+
+    solver TEXT NOT NULL,  -- name of the solver
+    solver_args JSONB NOT NULL,  -- any solver-specific parameters, just to understand what's going on
+    invocation_id INTEGER NOT NULL REFERENCES invocations_raw(id),
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS submissions(
+
+    submission_id INTEGER PRIMARY KEY,
+
+    problem_id INTEGER NOT NULL,  -- could be foreign key, but maybe we don't want to bother with the "problems" table
+    solution_id INTEGER NOT NULL REFERENCES solutions(id),
+
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Insert into good_submissions when the API returns a SUCCEEDED in the status field
+CREATE TABLE IF NOT EXISTS good_submissions(
+
+    submission_id INTEGER PRIMARY KEY REFERENCES submissions(submission_id),
+    cost BIGINT NOT NULL,
+    file_url TEXT NOT NULL,
+
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Insert into bad_submissions when the API returns a FAILED in the status field
+CREATE TABLE IF NOT EXISTS bad_submissions(
+
+    submission_id INTEGER PRIMARY KEY REFERENCES submissions(submission_id),
+    error TEXT NOT NULL,
+    file_url TEXT NOT NULL,
+
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS lower_bounds(
+    problem_id INTEGER PRIMARY KEY,
+    bound BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,8 @@
+-- SHA-256 of the canonical move list, see store::content_hash().
+-- NULL for rows uploaded before this migration until backfill_content_hashes is run,
+-- and for old duplicates, which get duplicate_of instead.
+ALTER TABLE solutions ADD COLUMN IF NOT EXISTS content_hash TEXT;
+ALTER TABLE solutions ADD COLUMN IF NOT EXISTS duplicate_of INTEGER REFERENCES solutions(id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS solutions_problem_id_content_hash
+ON solutions(problem_id, content_hash);
@@ -0,0 +1,3 @@
+-- For solutions derived from other solutions, see store::Derivation.
+ALTER TABLE solutions ADD COLUMN IF NOT EXISTS parent_id INTEGER REFERENCES solutions(id);
+ALTER TABLE solutions ADD COLUMN IF NOT EXISTS derivation TEXT;
@@ -0,0 +1,2 @@
+-- See solver_args.rs. NULL for solutions uploaded before it.
+ALTER TABLE solutions ADD COLUMN IF NOT EXISTS solver_args_version INTEGER;
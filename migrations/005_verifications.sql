@@ -0,0 +1,10 @@
+-- Latest result of replaying each solution, see verify_solutions entry point.
+-- error IS NULL for valid solutions.
+CREATE TABLE IF NOT EXISTS verifications(
+    solution_id INTEGER PRIMARY KEY REFERENCES solutions(id),
+    moves_cost BIGINT,
+    image_distance BIGINT,
+    error TEXT,
+    invocation_id INTEGER NOT NULL REFERENCES invocations_raw(id),
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Successful submissions where the server's cost differs from our score, see reconcile_scores entry point.
+CREATE TABLE IF NOT EXISTS score_mismatches(
+    submission_id INTEGER PRIMARY KEY REFERENCES submissions(submission_id),
+    solution_id INTEGER NOT NULL REFERENCES solutions(id),
+    our_score BIGINT NOT NULL,
+    their_score BIGINT NOT NULL,
+    suspect_move INTEGER,  -- index in the solution
+    explanation TEXT NOT NULL,
+    invocation_id INTEGER NOT NULL REFERENCES invocations_raw(id),
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Why failed submissions failed, see triage_submissions entry point.
+CREATE TABLE IF NOT EXISTS submission_triage(
+    submission_id INTEGER PRIMARY KEY REFERENCES submissions(submission_id),
+    kind TEXT NOT NULL,  -- see store::FailureKind
+    move_index INTEGER,
+    details TEXT NOT NULL,
+    invocation_id INTEGER NOT NULL REFERENCES invocations_raw(id),
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL
+);
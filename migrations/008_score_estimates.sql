@@ -0,0 +1,5 @@
+-- Grid bounds only hold for solutions without swaps, so these are estimates,
+-- see score_estimate.rs.
+ALTER TABLE lower_bounds RENAME TO score_estimates;
+ALTER TABLE score_estimates RENAME COLUMN bound TO estimate;
+UPDATE score_estimates SET kind = kind || ' (no swaps)' WHERE kind LIKE 'grid %';
@@ -0,0 +1,6 @@
+-- submission_triage.invocation_id is the invocation that produced the failing solution,
+-- not the triage run that used to be recorded there.
+ALTER TABLE submission_triage ALTER COLUMN invocation_id DROP NOT NULL;
+UPDATE submission_triage AS t SET invocation_id = s.invocation_id
+FROM submissions AS sub JOIN solutions AS s ON s.id = sub.solution_id
+WHERE sub.submission_id = t.submission_id;
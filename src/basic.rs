@@ -55,7 +55,11 @@ impl BlockId {
     }
 
     pub fn parse(s: &str) -> BlockId {
-        BlockId(s.split('.').map(|p| p.parse().unwrap()).collect())
+        BlockId::try_parse(s).unwrap_or_else(|| panic!("invalid block id {:?}", s))
+    }
+
+    pub fn try_parse(s: &str) -> Option<BlockId> {
+        s.split('.').map(|p| p.parse().ok()).collect::<Option<Vec<usize>>>().map(BlockId)
     }
 }
 
@@ -136,8 +140,12 @@ impl std::fmt::Display for Move {
 
 impl Move {
     pub fn parse_many(s: &str) -> Vec<Move> {
+        Move::try_parse_many(s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_parse_many(s: &str) -> Result<Vec<Move>, String> {
         let mut moves = vec![];
-        for line in s.split_terminator('\n') {
+        for (i, line) in s.split_terminator('\n').enumerate() {
             let line = line.trim();
             if line.is_empty() {
                 continue;
@@ -145,90 +153,80 @@ impl Move {
             if line.starts_with('#') {
                 continue;
             }
-            moves.push(Move::parse(line));
+            moves.push(Move::try_parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?);
         }
-        moves
+        Ok(moves)
     }
+
     pub fn parse(s: &str) -> Move {
-        let s = s.replace(' ', "");
+        Move::try_parse(s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_parse(line: &str) -> Result<Move, String> {
+        let err = || format!("can't parse move {:?}", line);
+        let s = line.replace(' ', "");
+        let bracketed = |s: &str| s.strip_prefix('[')?.strip_suffix(']').map(str::to_owned);
         if let Some(s) = s.strip_prefix("color") {
-            let (block_id, s) = strip_block_id(s);
-            let s = s.strip_prefix('[').unwrap();
-            let s = s.strip_suffix(']').unwrap();
-            let mut it = s.split(',');
-            let color = Color([
-                it.next().unwrap().parse().unwrap(),
-                it.next().unwrap().parse().unwrap(),
-                it.next().unwrap().parse().unwrap(),
-                it.next().unwrap().parse().unwrap(),
-            ]);
-            assert!(it.next().is_none());
-            return Move::ColorMove { block_id, color };
+            let (block_id, s) = strip_block_id(s).ok_or_else(err)?;
+            let s = bracketed(s).ok_or_else(err)?;
+            let parts = s.split(',').map(|p| p.parse().ok()).collect::<Option<Vec<u8>>>().ok_or_else(err)?;
+            let color = Color(parts.try_into().map_err(|_| err())?);
+            return Ok(Move::ColorMove { block_id, color });
         }
         if let Some(s) = s.strip_prefix("cut") {
-            let (block_id, s) = strip_block_id(s);
-            if let Some(s) = s.strip_prefix("[x]") {
-                let line_number = s
-                    .strip_prefix('[')
-                    .unwrap()
-                    .strip_suffix(']')
-                    .unwrap()
-                    .parse()
-                    .unwrap();
-                return Move::LCut {
-                    block_id,
-                    orientation: Vertical,
-                    line_number,
-                };
-            } else if let Some(s) = s.strip_prefix("[y]") {
-                let line_number = s
-                    .strip_prefix('[')
-                    .unwrap()
-                    .strip_suffix(']')
-                    .unwrap()
-                    .parse()
-                    .unwrap();
-                return Move::LCut {
+            let (block_id, s) = strip_block_id(s).ok_or_else(err)?;
+            let orientation = if s.starts_with("[x]") {
+                Some(Vertical)
+            } else if s.starts_with("[y]") {
+                Some(Horizontal)
+            } else {
+                None
+            };
+            if let Some(orientation) = orientation {
+                let line_number = bracketed(&s[3..]).and_then(|n| n.parse().ok()).ok_or_else(err)?;
+                return Ok(Move::LCut {
                     block_id,
-                    orientation: Horizontal,
+                    orientation,
                     line_number,
-                };
+                });
             } else {
-                let s = s.strip_prefix('[').unwrap();
-                let s = s.strip_suffix(']').unwrap();
-                let mut it = s.split(',');
-                let x = it.next().unwrap().parse().unwrap();
-                let y = it.next().unwrap().parse().unwrap();
-                assert!(it.next().is_none());
-                return Move::PCut { block_id, x, y };
+                let s = bracketed(s).ok_or_else(err)?;
+                let (x, y) = s.split_once(',').ok_or_else(err)?;
+                let x = x.parse().map_err(|_| err())?;
+                let y = y.parse().map_err(|_| err())?;
+                return Ok(Move::PCut { block_id, x, y });
             }
         }
         if let Some(s) = s.strip_prefix("swap") {
-            let (block_id1, s) = strip_block_id(s);
-            let (block_id2, s) = strip_block_id(s);
-            assert_eq!(s, "");
-            return Move::Swap {
+            let (block_id1, s) = strip_block_id(s).ok_or_else(err)?;
+            let (block_id2, s) = strip_block_id(s).ok_or_else(err)?;
+            if !s.is_empty() {
+                return Err(err());
+            }
+            return Ok(Move::Swap {
                 block_id1,
                 block_id2,
-            };
+            });
         }
         if let Some(s) = s.strip_prefix("merge") {
-            let (block_id1, s) = strip_block_id(s);
-            let (block_id2, s) = strip_block_id(s);
-            assert_eq!(s, "");
-            return Move::Merge {
+            let (block_id1, s) = strip_block_id(s).ok_or_else(err)?;
+            let (block_id2, s) = strip_block_id(s).ok_or_else(err)?;
+            if !s.is_empty() {
+                return Err(err());
+            }
+            return Ok(Move::Merge {
                 block_id1,
                 block_id2,
-            };
+            });
         }
-        panic!("unrecognized move {:?}", s);
+        Err(format!("unrecognized move {:?}", line))
     }
 }
 
-fn strip_block_id(s: &str) -> (BlockId, &str) {
-    let s = s.strip_prefix('[').unwrap();
-    let (block_id, s) = s.split_once(']').unwrap();
-    (BlockId::parse(block_id), s)
+fn strip_block_id(s: &str) -> Option<(BlockId, &str)> {
+    let s = s.strip_prefix('[')?;
+    let (block_id, s) = s.split_once(']')?;
+    Some((BlockId::try_parse(block_id)?, s))
 }
 
 fn roundtrip(s: &str) {
@@ -247,6 +245,38 @@ fn test_string_to_move_and_back() {
     roundtrip("merge [0.1] [7.2]");
 }
 
+#[test]
+fn test_try_parse() {
+    assert!(Move::try_parse("cut [0.1] [z] [11]").is_err());
+    assert!(Move::try_parse("color [0] [1, 2, 3]").is_err());
+    assert!(Move::try_parse("color [0] [1, 2, 3, 256]").is_err());
+    assert!(Move::try_parse("swap [0.x] [1]").is_err());
+    assert!(Move::try_parse("merge [0] [1] [2]").is_err());
+    assert!(Move::try_parse("paint [0]").is_err());
+    let e = Move::try_parse_many("cut [0] [x] [10]\n\ncut [0] [x]").unwrap_err();
+    assert!(e.starts_with("line 3:"), "{}", e);
+}
+
+#[test]
+fn test_try_apply_move() {
+    let problem = Problem::load(1);
+    let mut painter = PainterState::new(&problem);
+    let moves = Move::parse_many("
+        cut [0] [x] [100]
+        cut [0.0] [y] [100]
+    ");
+    for m in &moves {
+        painter.try_apply_move(m).unwrap();
+    }
+    let err = |s: &str, painter: &mut PainterState| painter.try_apply_move(&Move::parse(s)).err();
+    assert_eq!(err("color [0] [1, 2, 3, 4]", &mut painter), Some(MoveError::InvalidBlockId(BlockId::root(0))));
+    assert!(matches!(err("cut [0.1] [x] [50]", &mut painter), Some(MoveError::Geometry(_))));
+    assert!(matches!(err("swap [0.0.0] [0.1]", &mut painter), Some(MoveError::Geometry(_))));
+    assert!(matches!(err("merge [0.0.1] [0.1]", &mut painter), Some(MoveError::Geometry(_))));
+    assert_eq!(painter.moves.len(), 2);
+    assert_eq!(err("merge [0.0.0] [0.0.1]", &mut painter), None);
+}
+
 #[cfg(test)]
 #[test]
 fn test_move_to_string() {
@@ -537,6 +567,21 @@ pub struct PainterState<'a> {
     pub moves: Vec<Move>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    InvalidBlockId(BlockId),
+    Geometry(String),
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::InvalidBlockId(id) => write!(f, "no block [{}]", id),
+            MoveError::Geometry(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 pub struct ApplyMoveResult {
     pub cost: i64,
     pub new_block_ids: Vec<BlockId>, // new block IDs for "cut" and "merge" moves
@@ -603,6 +648,59 @@ impl<'a> PainterState<'a> {
         self.moves.pop();
     }
 
+    // Like apply_move(), but returns an error instead of panicking on invalid moves.
+    pub fn try_apply_move(&mut self, m: &Move) -> Result<ApplyMoveResult, MoveError> {
+        self.check_move(m)?;
+        Ok(self.apply_move(m))
+    }
+
+    pub fn check_move(&self, m: &Move) -> Result<(), MoveError> {
+        let shape = |id: &BlockId| {
+            self.blocks.get(id).map(|b| b.shape).ok_or_else(|| MoveError::InvalidBlockId(id.clone()))
+        };
+        let geometry = |msg: &str| Err(MoveError::Geometry(format!("{}: {}", m, msg)));
+        match m {
+            PCut { block_id, x, y } => {
+                let s = shape(block_id)?;
+                if !(s.x1 < *x && *x < s.x2 && s.y1 < *y && *y < s.y2) {
+                    return geometry(&format!("point is not inside {:?}", s));
+                }
+            }
+            LCut { block_id, orientation, line_number } => {
+                let s = shape(block_id)?;
+                let (lo, hi) = match orientation {
+                    Horizontal => (s.y1, s.y2),
+                    Vertical => (s.x1, s.x2),
+                };
+                if !(lo < *line_number && *line_number < hi) {
+                    return geometry(&format!("line is not inside {:?}", s));
+                }
+            }
+            ColorMove { block_id, .. } => {
+                shape(block_id)?;
+            }
+            Swap { block_id1, block_id2 } => {
+                let (s1, s2) = (shape(block_id1)?, shape(block_id2)?);
+                if block_id1 == block_id2 {
+                    return geometry("swapping block with itself");
+                }
+                if s1.width() != s2.width() || s1.height() != s2.height() {
+                    return geometry(&format!("blocks have different shapes {:?} {:?}", s1, s2));
+                }
+            }
+            Merge { block_id1, block_id2 } => {
+                let (s1, s2) = (shape(block_id1)?, shape(block_id2)?);
+                if block_id1 == block_id2 {
+                    return geometry("merging block with itself");
+                }
+                if merge_shapes(s1, s2).is_none() {
+                    return geometry(&format!("blocks are not adjacent {:?} {:?}", s1, s2));
+                }
+            }
+        }
+        Ok(())
+    }
+
     // Returns the cost of the applied move
     pub fn apply_move(&mut self, m: &Move) -> ApplyMoveResult {
         let mut new_block_ids = vec![];
@@ -919,14 +1017,28 @@ pub struct Problem {
 }
 
 impl Problem {
+    // All problems in data/problems/, sorted.
+    pub fn all_ids() -> Vec<i32> {
+        let mut ids: Vec<i32> = std::fs::read_dir(project_path("data/problems")).unwrap()
+            .filter_map(|e| e.unwrap().file_name().to_str()?.strip_suffix(".png")?.parse().ok())
+            .collect();
+        ids.sort();
+        ids
+    }
+
     pub fn load(problem_id: i32) -> Problem {
-        let target = Image::load(&project_path(format!("data/problems/{}.png", problem_id)));
-        let initial = project_path(format!("data/problems/{}.initial.json", problem_id));
+        Problem::load_from(&project_path("data/problems"), problem_id)
+    }
+
+    // From a directory laid out like data/problems/.
+    pub fn load_from(dir: &std::path::Path, problem_id: i32) -> Problem {
+        let target = Image::load(&dir.join(format!("{}.png", problem_id)));
+        let initial = dir.join(format!("{}.initial.json", problem_id));
 
         let mut start_blocks: Vec<(BlockId, Block)> = vec![];
 
         //let source_img_path = project_path(format!("data/problems/{}.source.png", problem_id));
-        let initial_img_path = project_path(format!("data/problems/{}.initial.png", problem_id));
+        let initial_img_path = dir.join(format!("{}.initial.png", problem_id));
         let initial_img = if initial_img_path.exists() {
             Some(Image::load(&initial_img_path))
         } else {
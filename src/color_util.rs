@@ -375,3 +375,149 @@ pub fn adjust_colors(problem: &Problem, moves: &Vec<Move>) -> Vec<Move> {
         })
         .collect()
 }
+
+// Unlike adjust_colors(), tells apart color moves that happen to use the same color,
+// and only counts the pixels each move still owns in the final picture.
+// To track ownership through cuts, swaps and merges, the moves are replayed
+// with every color replaced by a unique tag color.
+// Color moves that don't own any pixels are dropped.
+pub fn reoptimize_color_moves(problem: &Problem, moves: &[Move]) -> Vec<Move> {
+    let _t = crate::stats_timer!("reoptimize_color_moves").time_it();
+    let mut painter = PainterState::new(problem);
+    let mut taken: HashSet<Color> = HashSet::default();
+    for block in painter.blocks.values() {
+        for (_, pic) in &block.pieces {
+            if let Pic::Unicolor(c) = pic {
+                taken.insert(*c);
+            }
+        }
+    }
+
+    let mut tags: HashMap<Color, usize> = HashMap::default();
+    let mut next_tag = 0u32;
+    for (i, mv) in moves.iter().enumerate() {
+        match mv {
+            Move::ColorMove { block_id, color: _ } => {
+                let tag = loop {
+                    let tag = Color(next_tag.to_le_bytes());
+                    next_tag += 1;
+                    if !taken.contains(&tag) {
+                        break tag;
+                    }
+                };
+                tags.insert(tag, i);
+                painter.apply_move(&Move::ColorMove { block_id: block_id.clone(), color: tag });
+            }
+            _ => {
+                painter.apply_move(mv);
+            }
+        }
+    }
+
+    let mut freqs: HashMap<usize, HashMap<Color, f64>> = HashMap::default();
+    for block in painter.blocks.values() {
+        for (shape, pic) in &block.pieces {
+            let Pic::Unicolor(c) = pic else { continue };
+            let Some(&i) = tags.get(c) else { continue };
+            let cf = freqs.entry(i).or_default();
+            for x in shape.x1..shape.x2 {
+                for y in shape.y1..shape.y2 {
+                    *cf.entry(problem.target.get_pixel(x, y)).or_default() += 1.0;
+                }
+            }
+        }
+    }
+
+    moves
+        .iter()
+        .enumerate()
+        .filter_map(|(i, mv)| match mv {
+            Move::ColorMove { block_id, color } => {
+                let cf = freqs.get(&i)?;
+                let new_color = optimal_color_for_color_freqs(cf);
+                // Weiszfeld + climb is not guaranteed to find the optimum.
+                let color = if color_freqs_distance(cf, new_color) < color_freqs_distance(cf, *color) {
+                    new_color
+                } else {
+                    *color
+                };
+                Some(Move::ColorMove { block_id: block_id.clone(), color })
+            }
+            _ => Some(mv.clone()),
+        })
+        .collect()
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RecolorArgs {
+    base_solution: i32,
+}
+crate::solver_args!(RecolorArgs, "recolor", 1);
+
+crate::entry_point!("recolor", recolor, _EP2);
+fn recolor() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: String = pargs.value_from_str("--problem").unwrap();
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = crate::invocation::InvocationGuard::start();
+
+    let mut store = crate::store::open();
+    for problem_id in crate::util::parse_range(&problems) {
+        let problem = Problem::load(problem_id);
+        let Some(best) = store.best_solution(problem_id) else { continue };
+        let moves = reoptimize_color_moves(&problem, &Move::parse_many(&store.solution_data(best.id)));
+
+        let mut painter = PainterState::new(&problem);
+        for mv in &moves {
+            painter.apply_move(mv);
+        }
+        let score = painter.cost + image_distance(&problem.target, &painter.render()).round() as i64;
+        eprintln!("problem {}: {} -> {}", problem_id, best.score, score);
+        if score >= best.score {
+            continue;
+        }
+
+        let mut tx = store.transaction();
+        let solver_args = serde_json::to_value(RecolorArgs { base_solution: best.id }).unwrap();
+        let derivation = crate::store::Derivation { parent_id: best.id, label: "recolor" };
+        crate::uploader::upload_solution(&mut *tx, problem_id, &moves, "recolor", &solver_args, invocation.id(), Some(derivation));
+        if dry_run {
+            eprintln!("But not really, because it was a --dry-run!");
+        } else {
+            tx.commit();
+        }
+    }
+}
+
+#[test]
+fn test_reoptimize_color_moves() {
+    let mut problem = Problem::load(1);
+    problem.target = Image::new(problem.width, problem.height, Color([200, 0, 0, 255]));
+    problem.target.fill_rect(Shape { x1: 200, y1: 0, x2: problem.width, y2: problem.height }, Color([0, 0, 200, 255]));
+
+    let gray = Color([100, 0, 100, 255]);
+    let moves = Move::parse_many("
+        color [0] [1, 2, 3, 4]
+        cut [0] [x] [200]
+        color [0.0] [100, 0, 100, 255]
+        color [0.1] [100, 0, 100, 255]
+        swap [0.0] [0.1]
+        merge [0.0] [0.1]
+    ");
+    assert!(matches!(moves[2], Move::ColorMove { color, .. } if color == gray));
+
+    let new_moves = reoptimize_color_moves(&problem, &moves);
+    // The first color move is entirely painted over.
+    assert_eq!(new_moves.len(), moves.len() - 1);
+    // Because of the swap, [0.0] ends up on the right.
+    assert_eq!(new_moves[1], Move::ColorMove { block_id: BlockId::parse("0.0"), color: Color([0, 0, 200, 255]) });
+    assert_eq!(new_moves[2], Move::ColorMove { block_id: BlockId::parse("0.1"), color: Color([200, 0, 0, 255]) });
+
+    let mut painter = PainterState::new(&problem);
+    for mv in &new_moves {
+        painter.apply_move(mv);
+    }
+    assert_eq!(painter.render(), problem.target);
+}
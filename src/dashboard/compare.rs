@@ -0,0 +1,184 @@
+use askama::Template;
+
+use crate::basic::*;
+use crate::store::SolutionRow;
+
+use super::dev_server::{Request, ResponseBuilder, HandlerResult};
+use super::render::{closer_heatmap, png_data_uri};
+use super::solutions::{replay, Replay, MOVE_KINDS};
+
+// Handles "/compare?a=ID&b=ID".
+pub fn handler(req: Request, resp: ResponseBuilder) -> HandlerResult {
+    let (Some(a), Some(b)) = (req.query_args.remove("a"), req.query_args.remove("b")) else {
+        return resp.code("400 Bad Request").body("usage: /compare?a=ID&b=ID");
+    };
+    let (Ok(a), Ok(b)) = (a.trim().parse::<i32>(), b.trim().parse::<i32>()) else {
+        return resp.code("400 Bad Request").body(format!("solution ids should be numbers, got {:?} and {:?}", a, b));
+    };
+
+    let mut store = crate::store::open();
+    let (Some(row_a), Some(row_b)) = (store.find_solution(a), store.find_solution(b)) else {
+        return resp.code("404 Not Found").body(format!("no sol/{} or sol/{}", a, b));
+    };
+    let rows = [row_a, row_b];
+    if rows[0].problem_id != rows[1].problem_id {
+        return resp.code("400 Bad Request").body(format!(
+            "sol/{} and sol/{} are for different problems ({} and {})",
+            a, b, rows[0].problem_id, rows[1].problem_id));
+    }
+    let problem = Problem::load(rows[0].problem_id);
+    let mut replays = vec![];
+    for row in &rows {
+        let moves = Move::try_parse_many(&store.solution_data(row.id));
+        match moves.and_then(|moves| replay(&problem, &moves)) {
+            Ok(r) => replays.push(r),
+            Err(e) => return resp.code("422 Unprocessable Entity").body(format!("Can't replay sol/{}: {}", row.id, e)),
+        }
+    }
+
+    let (heatmap, a_closer, b_closer) = closer_heatmap(&replays[0].img, &replays[1].img, &problem.target);
+    let move_kinds = MOVE_KINDS.iter().map(|&kind| MoveKindRow {
+        kind,
+        a: move_kind_stats(&replays[0], kind),
+        b: move_kind_stats(&replays[1], kind),
+    }).collect();
+    let histogram = block_size_histogram(&replays[0].block_sizes, &replays[1].block_sizes);
+    let [a_row, b_row] = rows;
+    let s = CompareTemplate {
+        problem_id: a_row.problem_id,
+        a_render_uri: png_data_uri(&replays[0].img),
+        b_render_uri: png_data_uri(&replays[1].img),
+        a_num_moves: replays[0].move_counts.values().sum(),
+        b_num_moves: replays[1].move_counts.values().sum(),
+        a_num_blocks: replays[0].block_sizes.len(),
+        b_num_blocks: replays[1].block_sizes.len(),
+        a: a_row,
+        b: b_row,
+        heatmap_uri: png_data_uri(&heatmap),
+        a_closer,
+        b_closer,
+        move_kinds,
+        histogram,
+    }.render().unwrap();
+    resp.code("200 OK").body(s)
+}
+
+struct MoveKindRow {
+    kind: &'static str,
+    a: (usize, i64),
+    b: (usize, i64),
+}
+
+// (count, cost)
+fn move_kind_stats(r: &Replay, kind: &str) -> (usize, i64) {
+    (r.move_counts.get(kind).cloned().unwrap_or(0), r.cost_breakdown.get(kind).cloned().unwrap_or(0))
+}
+
+struct HistogramBucket {
+    // sizes in lo..hi
+    lo: i32,
+    hi: i32,
+    a: usize,
+    b: usize,
+    // bar widths in px
+    a_width: usize,
+    b_width: usize,
+}
+
+// Power-of-two buckets over the range of sizes present in either solution.
+fn block_size_histogram(a: &[i32], b: &[i32]) -> Vec<HistogramBucket> {
+    let bucket = |size: i32| size.ilog2() as usize;
+    let Some(max) = a.iter().chain(b).map(|&s| bucket(s)).max() else { return vec![] };
+    let min = a.iter().chain(b).map(|&s| bucket(s)).min().unwrap();
+    let count = |sizes: &[i32], k: usize| sizes.iter().filter(|&&s| bucket(s) == k).count();
+    let counts: Vec<(usize, usize, usize)> = (min..=max).map(|k| (k, count(a, k), count(b, k))).collect();
+    let max_count = counts.iter().map(|&(_, a, b)| a.max(b)).max().unwrap();
+    counts.into_iter().map(|(k, a, b)| HistogramBucket {
+        lo: 1 << k,
+        hi: 1 << (k + 1),
+        a,
+        b,
+        a_width: 200 * a / max_count,
+        b_width: 200 * b / max_count,
+    }).collect()
+}
+
+#[derive(Template)]
+#[template(ext = "html", source = r#"
+{% extends "base.html" %}
+{% block title %}compare sol/{{ a.id }} sol/{{ b.id }}{% endblock %}
+{% block body %}
+<style>
+.bar { display: inline-block; height: 0.8em; }
+</style>
+<p>
+    <a href="/problem/{{ problem_id }}">problem {{ problem_id }}</a>,
+    <a href="/compare?a={{ b.id }}&b={{ a.id }}">swap sides</a>
+</p>
+<table>
+<thead>
+<tr><th></th><th>A</th><th>B</th></tr>
+</thead>
+<tr><td>solution</td><td><a href="/solution/{{ a.id }}">sol/{{ a.id }}</a></td><td><a href="/solution/{{ b.id }}">sol/{{ b.id }}</a></td></tr>
+<tr><td>solver</td><td>{{ a.solver_name }}</td><td>{{ b.solver_name }}</td></tr>
+<tr><td>score</td><td>{{ a.score }}</td><td>{{ b.score }}</td></tr>
+<tr><td>image distance</td><td>{{ a.image_distance }}</td><td>{{ b.image_distance }}</td></tr>
+<tr><td>moves cost</td><td>{{ a.moves_cost }}</td><td>{{ b.moves_cost }}</td></tr>
+<tr><td>moves</td><td>{{ a_num_moves }}</td><td>{{ b_num_moves }}</td></tr>
+<tr><td>blocks</td><td>{{ a_num_blocks }}</td><td>{{ b_num_blocks }}</td></tr>
+{% for m in move_kinds %}
+<tr><td>{{ m.kind }} (count: cost)</td><td>{{ m.a.0 }}: {{ m.a.1 }}</td><td>{{ m.b.0 }}: {{ m.b.1 }}</td></tr>
+{% endfor %}
+</table>
+
+<p>
+    <img src="{{ a_render_uri }}" title="A"/>
+    <img src="/data/problems/{{ problem_id }}.png" title="target"/>
+    <img src="{{ b_render_uri }}" title="B"/>
+</p>
+<p>
+    <img src="{{ heatmap_uri }}"/><br>
+    Closer to the target: <span style="color: blue">A</span> on {{ a_closer }} pixels,
+    <span style="color: darkorange">B</span> on {{ b_closer }} pixels.
+</p>
+
+<h3>Block sizes</h3>
+<table>
+<thead>
+<tr><th>size</th><th>A</th><th>B</th></tr>
+</thead>
+{% for h in histogram %}
+<tr>
+    <td>{{ h.lo }}..{{ h.hi }}</td>
+    <td><span class="bar" style="width: {{ h.a_width }}px; background: blue"></span> {{ h.a }}</td>
+    <td><span class="bar" style="width: {{ h.b_width }}px; background: darkorange"></span> {{ h.b }}</td>
+</tr>
+{% endfor %}
+</table>
+{% endblock %}
+"#)]
+struct CompareTemplate {
+    problem_id: i32,
+    a: SolutionRow,
+    b: SolutionRow,
+    a_render_uri: String,
+    b_render_uri: String,
+    a_num_moves: usize,
+    b_num_moves: usize,
+    a_num_blocks: usize,
+    b_num_blocks: usize,
+    heatmap_uri: String,
+    a_closer: usize,
+    b_closer: usize,
+    move_kinds: Vec<MoveKindRow>,
+    histogram: Vec<HistogramBucket>,
+}
+
+#[test]
+fn test_block_size_histogram() {
+    let h = block_size_histogram(&[1, 3, 4], &[7, 7, 16]);
+    let buckets: Vec<_> = h.iter().map(|b| (b.lo, b.hi, b.a, b.b)).collect();
+    assert_eq!(buckets, vec![(1, 2, 1, 0), (2, 4, 1, 0), (4, 8, 1, 2), (8, 16, 0, 0), (16, 32, 0, 1)]);
+    assert_eq!(h[2].b_width, 200);
+    assert!(block_size_histogram(&[], &[]).is_empty());
+}
@@ -0,0 +1,435 @@
+// Team state as shown on the dashboard.
+// Both the HTML pages and the JSON API (see json_api.rs) are built from these.
+
+use std::collections::HashMap as StdHashMap;
+use fxhash::FxHashMap as HashMap;
+
+use crate::invocation::Invocation;
+use crate::store::{datetime_format, ScoreMismatch, SolutionRow, Store, SubmissionResult, Triage, Verification};
+use crate::util::DateTime;
+
+// Result of the latest verify_solutions run, if any.
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "status", content = "details", rename_all = "snake_case")]
+pub enum VerificationMark {
+    Unverified,
+    Ok,
+    Invalid(String),
+    Changed(String),
+}
+
+impl VerificationMark {
+    fn new(v: Option<&Verification>, row: &SolutionRow) -> VerificationMark {
+        match v {
+            None => VerificationMark::Unverified,
+            Some(v) if !v.valid() => VerificationMark::Invalid(v.error.clone().unwrap()),
+            Some(v) if v.score_changed(row) => VerificationMark::Changed(format!(
+                "replayed: cost={} dist={}", v.moves_cost.unwrap(), v.image_distance.unwrap())),
+            Some(_) => VerificationMark::Ok,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct SolutionView {
+    pub id: i32,
+    pub problem_id: i32,
+    pub moves_cost: i64,
+    pub image_distance: i64,
+    pub score: i64,
+    pub solver_name: String,
+    pub solver_args: serde_json::Value,
+    pub invocation_id: i32,
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+    pub parent_id: Option<i32>,
+    pub derivation: Option<String>,
+    // see score_estimate.rs, not a hard bound
+    pub score_estimate: Option<i64>,
+    pub verification: VerificationMark,
+    // best among the listed solutions for the same problem
+    pub best: bool,
+}
+
+impl SolutionView {
+    pub fn gap(&self) -> String {
+        match self.score_estimate {
+            Some(est) if est > 0 => format!("{:.2}", self.score as f64 / est as f64),
+            _ => String::new(),
+        }
+    }
+}
+
+// Score estimates and verifications to attach to solution rows.
+struct Annotations {
+    score_estimates: HashMap<i32, i64>,
+    verifications: HashMap<i32, Verification>,
+}
+
+impl Annotations {
+    fn load(store: &mut dyn Store) -> Annotations {
+        Annotations {
+            score_estimates: store.score_estimates().into_iter()
+                .map(|(problem_id, est)| (problem_id, est.estimate)).collect(),
+            verifications: store.verifications().into_iter()
+                .map(|v| (v.solution_id, v)).collect(),
+        }
+    }
+
+    fn view(&self, row: SolutionRow, best: bool) -> SolutionView {
+        SolutionView {
+            score_estimate: self.score_estimates.get(&row.problem_id).cloned(),
+            verification: VerificationMark::new(self.verifications.get(&row.id), &row),
+            id: row.id,
+            problem_id: row.problem_id,
+            moves_cost: row.moves_cost,
+            image_distance: row.image_distance,
+            score: row.score,
+            solver_name: row.solver_name,
+            solver_args: row.solver_args,
+            invocation_id: row.invocation_id,
+            timestamp: row.timestamp,
+            parent_id: row.parent_id,
+            derivation: row.derivation,
+            best,
+        }
+    }
+}
+
+// Query args of /solution/ and /api/solutions.
+#[derive(Default)]
+pub struct SolutionFilter {
+    pub problem_id: Option<i32>,
+    pub solver: Option<String>,
+    pub invocation_id: Option<i32>,
+    // all solutions instead of the best ones
+    pub archive: bool,
+    pub by_solver: bool,
+    pub top: Option<usize>,
+    pub valid_only: bool,
+}
+
+impl SolutionFilter {
+    // Err describes the first malformed arg.
+    pub fn from_query(query_args: &mut StdHashMap<&str, &str>) -> Result<SolutionFilter, String> {
+        fn number<T: std::str::FromStr>(query_args: &mut StdHashMap<&str, &str>, name: &str) -> Result<Option<T>, String> {
+            query_args.remove(name)
+                .map(|s| s.parse().map_err(|_| format!("{} should be a number, got {:?}", name, s)))
+                .transpose()
+        }
+        let flag = |s: Option<&str>| s.is_some_and(|x| x == "true");
+        Ok(SolutionFilter {
+            problem_id: number(query_args, "problem_id")?,
+            solver: query_args.remove("solver").map(|s| s.to_owned()),
+            invocation_id: number(query_args, "invocation_id")?,
+            archive: flag(query_args.remove("archive")),
+            by_solver: flag(query_args.remove("by_solver")),
+            top: number(query_args, "top")?,
+            valid_only: flag(query_args.remove("valid_only")),
+        })
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct SolutionList {
+    // sum of the best listed solution of each problem
+    pub total_score: i64,
+    // ordered by problem
+    pub solutions: Vec<SolutionView>,
+}
+
+// Up to n best rows for each key, ordered by problem and score.
+fn top_by<K: Eq + std::hash::Hash>(mut rows: Vec<SolutionRow>, n: usize, key: impl Fn(&SolutionRow) -> K) -> Vec<SolutionRow> {
+    rows.sort_by_key(|s| (s.problem_id, s.score, s.timestamp));
+    let mut counts: HashMap<K, usize> = HashMap::default();
+    rows.retain(|s| {
+        let c = counts.entry(key(s)).or_default();
+        *c += 1;
+        *c <= n
+    });
+    rows
+}
+
+pub fn solutions(store: &mut dyn Store, filter: &SolutionFilter) -> SolutionList {
+    store.set_exclude_invalid(filter.valid_only);
+    let rows = if filter.solver.is_some() || filter.invocation_id.is_some() {
+        // Bests among the matching solutions, not the matching ones among the global bests.
+        let rows = if filter.archive {
+            store.solutions(filter.problem_id)
+        } else {
+            store.leaderboard_candidates(filter.problem_id)
+        };
+        let rows: Vec<SolutionRow> = rows.into_iter()
+            .filter(|row| filter.solver.as_ref().is_none_or(|s| *s == row.solver_name))
+            .filter(|row| filter.invocation_id.is_none_or(|i| i == row.invocation_id))
+            .collect();
+        if filter.archive {
+            rows
+        } else if filter.by_solver {
+            top_by(rows, 1, |s| (s.problem_id, s.solver_name.clone()))
+        } else {
+            top_by(rows, filter.top.unwrap_or(1), |s| s.problem_id)
+        }
+    } else if filter.archive {
+        store.solutions(filter.problem_id)
+    } else if filter.by_solver {
+        store.solver_bests()
+    } else if let Some(n) = filter.top {
+        store.top_solutions(n)
+    } else {
+        store.best_solutions()
+    };
+    let rows: Vec<SolutionRow> = rows.into_iter()
+        .filter(|row| filter.problem_id.is_none_or(|p| p == row.problem_id))
+        .collect();
+
+    let mut best: HashMap<i32, &SolutionRow> = HashMap::default();
+    for row in &rows {
+        let b = best.entry(row.problem_id).or_insert(row);
+        if (row.score, row.timestamp) < (b.score, b.timestamp) {
+            *b = row;
+        }
+    }
+    let total_score = best.values().map(|row| row.score).sum();
+    let best_ids: Vec<i32> = best.values().map(|row| row.id).collect();
+
+    let annotations = Annotations::load(store);
+    let mut solutions: Vec<SolutionView> = rows.into_iter()
+        .map(|row| {
+            let is_best = best_ids.contains(&row.id);
+            annotations.view(row, is_best)
+        })
+        .collect();
+    // stable, so the store order is kept within a problem
+    solutions.sort_by_key(|s| s.problem_id);
+    SolutionList { total_score, solutions }
+}
+
+pub fn solution(store: &mut dyn Store, id: i32) -> Option<SolutionView> {
+    let row = store.find_solution(id)?;
+    let best = store.best_solution(row.problem_id).is_some_and(|b| b.id == id);
+    Some(Annotations::load(store).view(row, best))
+}
+
+#[derive(serde::Serialize)]
+pub struct LeaderboardEntry {
+    pub problem_id: i32,
+    pub score_estimate: Option<i64>,
+    pub best: Option<SolutionView>,
+}
+
+// All problems with their best solutions, see store::leaderboard().
+pub fn leaderboard(store: &mut dyn Store, valid_only: bool) -> Vec<LeaderboardEntry> {
+    store.set_exclude_invalid(valid_only);
+    let annotations = Annotations::load(store);
+    crate::store::leaderboard(store, None).into_iter()
+        .map(|(problem_id, best)| LeaderboardEntry {
+            problem_id,
+            score_estimate: annotations.score_estimates.get(&problem_id).cloned(),
+            best: best.map(|row| annotations.view(row, true)),
+        })
+        .collect()
+}
+
+#[derive(serde::Serialize)]
+pub struct InvocationView {
+    pub id: i32,
+    pub status: String,
+    #[serde(with = "datetime_format")]
+    pub start_time: DateTime,
+    #[serde(with = "datetime_format")]
+    pub update_time: DateTime,
+    pub data: Invocation,
+}
+
+// Running ones first, then by last update, most recent first.
+pub fn invocations(store: &mut dyn Store) -> Vec<InvocationView> {
+    let mut rows: Vec<_> = store.invocations().into_iter().map(|row| {
+        InvocationView { id: row.id, status: row.status, start_time: row.start_time, update_time: row.update_time, data: row.data }
+    }).collect();
+    rows.sort_by_key(|r| if r.status == "RUN" { (2, r.start_time) } else { (1, r.update_time) });
+    rows.reverse();
+    rows
+}
+
+pub fn invocation(store: &mut dyn Store, id: i32) -> Option<InvocationView> {
+    let row = store.find_invocation(id)?;
+    Some(InvocationView { id, status: row.status, start_time: row.start_time, update_time: row.update_time, data: row.data })
+}
+
+#[derive(serde::Serialize)]
+pub struct GoodSubmission {
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+    pub submission_id: i32,
+    pub solver_name: String,
+    pub solver_args: serde_json::Value,
+    pub solution_id: i32,
+    pub problem_id: i32,
+    pub our_cost: i64,
+    pub their_cost: i64,
+    pub file_url: String,
+    // see reconcile_scores entry point
+    pub mismatch: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TriageMark {
+    pub kind: &'static str,
+    pub details: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct BadSubmission {
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+    pub submission_id: i32,
+    pub solver_name: String,
+    pub invocation_id: i32,
+    pub solution_id: i32,
+    pub problem_id: i32,
+    pub error: String,
+    pub file_url: String,
+    // see triage_submissions entry point
+    pub triage: Option<TriageMark>,
+}
+
+#[derive(serde::Serialize)]
+pub struct PendingSubmission {
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+    pub submission_id: i32,
+    pub solution_id: i32,
+    pub problem_id: i32,
+}
+
+// Most recent first.
+#[derive(serde::Serialize)]
+pub struct Submissions {
+    pub good: Vec<GoodSubmission>,
+    pub bad: Vec<BadSubmission>,
+    pub pending: Vec<PendingSubmission>,
+}
+
+pub fn submissions(store: &mut dyn Store) -> Submissions {
+    let mismatches: HashMap<i32, ScoreMismatch> = store.score_mismatches().into_iter()
+        .map(|m| (m.submission_id, m)).collect();
+    let triages: HashMap<i32, Triage> = store.triages().into_iter()
+        .map(|t| (t.submission_id, t)).collect();
+    let mut solutions: HashMap<i32, SolutionRow> = store.solutions(None).into_iter().map(|s| (s.id, s)).collect();
+    let mut res = Submissions { good: vec![], bad: vec![], pending: vec![] };
+    for sub in store.submissions().into_iter().rev() {
+        // duplicates aren't listed, but could have been submitted
+        let o = solutions.entry(sub.solution_id).or_insert_with(|| store.solution(sub.solution_id));
+        match sub.result {
+            SubmissionResult::Good { cost, file_url } => res.good.push(GoodSubmission {
+                submission_id: sub.submission_id, solution_id: sub.solution_id,
+                problem_id: sub.problem_id,
+                our_cost: o.score, their_cost: cost, file_url,
+                mismatch: mismatches.get(&sub.submission_id).map(|m| m.explanation.clone()),
+                solver_name: o.solver_name.clone(),
+                solver_args: o.solver_args.clone(),
+                timestamp: sub.timestamp,
+            }),
+            SubmissionResult::Bad { error, file_url } => res.bad.push(BadSubmission {
+                submission_id: sub.submission_id, solution_id: sub.solution_id,
+                problem_id: sub.problem_id, error, file_url,
+                triage: triages.get(&sub.submission_id)
+                    .map(|t| TriageMark { kind: t.kind.as_str(), details: t.details.clone() }),
+                solver_name: o.solver_name.clone(),
+                invocation_id: o.invocation_id,
+                timestamp: sub.timestamp,
+            }),
+            SubmissionResult::Pending => res.pending.push(PendingSubmission {
+                submission_id: sub.submission_id, solution_id: sub.solution_id,
+                problem_id: sub.problem_id,
+                timestamp: sub.timestamp,
+            }),
+        }
+    }
+    res
+}
+
+#[test]
+fn test_solutions_and_submissions() {
+    use crate::local_store::{insert_test_solution, TestStore};
+    use crate::basic::Move;
+    use crate::store::{canonical_text, content_hash, InsertedSolution, NewSolution};
+
+    let mut store = TestStore::new();
+    let mut tx = store.transaction();
+    let InsertedSolution::New(a) = insert_test_solution(&mut *tx, 1, 50, 1, 0) else { panic!() };
+    let InsertedSolution::New(b) = insert_test_solution(&mut *tx, 1, 20, 2, 7) else { panic!() };
+    let InsertedSolution::New(c) = insert_test_solution(&mut *tx, 2, 30, 1, 7) else { panic!() };
+    let moves = Move::parse_many("color [0] [9, 9, 9, 9]");
+    let InsertedSolution::New(d) = tx.insert_solution(&NewSolution {
+        problem_id: 1,
+        data: &canonical_text(&moves),
+        content_hash: &content_hash(&moves),
+        moves_cost: 80,
+        image_distance: 100,
+        solver_name: "other",
+        solver_args: &serde_json::Value::Null,
+        solver_args_version: None,
+        invocation_id: 0,
+        derivation: None,
+    }) else { panic!() };
+    tx.insert_submission(1, a, 10);
+    tx.insert_submission(1, b, 11);
+    tx.insert_submission(2, c, 12);
+    tx.record_submission_result(10, &SubmissionResult::Good { cost: 150, file_url: String::new() });
+    tx.record_submission_result(11, &SubmissionResult::Bad { error: "oops".to_owned(), file_url: String::new() });
+    tx.commit();
+
+    let ids = |list: &SolutionList| list.solutions.iter().map(|s| s.id).collect::<Vec<_>>();
+    let all = solutions(&mut *store, &SolutionFilter { archive: true, ..Default::default() });
+    assert_eq!(ids(&all), vec![a, b, d, c]);
+    assert_eq!(all.solutions.iter().filter(|s| s.best).map(|s| s.id).collect::<Vec<_>>(), vec![b, c]);
+    assert_eq!(all.total_score, 120 + 130);
+
+    let best = solutions(&mut *store, &SolutionFilter::default());
+    assert_eq!(ids(&best), vec![b, c]);
+    let by_inv = solutions(&mut *store, &SolutionFilter { archive: true, invocation_id: Some(0), ..Default::default() });
+    assert_eq!(ids(&by_inv), vec![a, d]);
+
+    // Neither holds the global best of problem 1, they should still get their own bests.
+    let other = solutions(&mut *store, &SolutionFilter { solver: Some("other".to_owned()), ..Default::default() });
+    assert_eq!(ids(&other), vec![d]);
+    assert!(other.solutions[0].best);
+    assert_eq!(other.total_score, 180);
+    let by_inv = solutions(&mut *store, &SolutionFilter { invocation_id: Some(0), ..Default::default() });
+    assert_eq!(ids(&by_inv), vec![a]);
+    assert!(by_inv.solutions[0].best);
+    assert_eq!(by_inv.total_score, 150);
+    let by_inv = solutions(&mut *store, &SolutionFilter { invocation_id: Some(0), by_solver: true, ..Default::default() });
+    assert_eq!(ids(&by_inv), vec![a, d]);
+    let test_solver = solutions(&mut *store, &SolutionFilter { solver: Some("test".to_owned()), top: Some(5), ..Default::default() });
+    assert_eq!(ids(&test_solver), vec![b, a, c]);
+    let p2 = solutions(&mut *store, &SolutionFilter { problem_id: Some(2), solver: Some("test".to_owned()), ..Default::default() });
+    assert_eq!(ids(&p2), vec![c]);
+    let none = solutions(&mut *store, &SolutionFilter { solver: Some("nope".to_owned()), ..Default::default() });
+    assert!(none.solutions.is_empty());
+
+    assert!(solution(&mut *store, b).unwrap().best);
+    assert!(!solution(&mut *store, a).unwrap().best);
+    assert!(solution(&mut *store, 999_999).is_none());
+    let json = serde_json::to_value(solution(&mut *store, a).unwrap()).unwrap();
+    assert_eq!(json["verification"]["status"], "unverified");
+
+    let subs = submissions(&mut *store);
+    assert_eq!(subs.good.iter().map(|s| s.submission_id).collect::<Vec<_>>(), vec![10]);
+    assert_eq!(subs.bad.iter().map(|s| s.submission_id).collect::<Vec<_>>(), vec![11]);
+    assert_eq!(subs.pending.iter().map(|s| s.submission_id).collect::<Vec<_>>(), vec![12]);
+}
+
+#[test]
+fn test_solution_filter_from_query() {
+    let mut args: StdHashMap<&str, &str> = [("problem_id", "3"), ("top", "2"), ("archive", "true")].into_iter().collect();
+    let f = SolutionFilter::from_query(&mut args).unwrap();
+    assert_eq!((f.problem_id, f.top, f.archive, f.by_solver), (Some(3), Some(2), true, false));
+    assert!(args.is_empty());
+
+    let mut args: StdHashMap<&str, &str> = [("top", "x")].into_iter().collect();
+    let Err(e) = SolutionFilter::from_query(&mut args) else { panic!() };
+    assert!(e.contains("top"));
+}
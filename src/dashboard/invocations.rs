@@ -1,46 +1,19 @@
-use std::time::SystemTime;
 use askama::Template;
 use super::dev_server::{Request, ResponseBuilder, HandlerResult};
 use crate::invocation::Invocation;
-use crate::util::DateTime;
+use super::data::{self, InvocationView};
 
-pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: ResponseBuilder) -> HandlerResult {
+pub fn handler(req: Request, resp: ResponseBuilder) -> HandlerResult {
     if req.path.is_empty() {  // list all invocations
-        let client = &mut state.lock().unwrap().client;
-
-        let raw_rows = client.query("
-        SELECT id, status, start_time, update_time, data FROM invocations
-        ", &[]).unwrap();
-        let mut rows: Vec<_> = raw_rows.into_iter().map(|row| {
-            let id: i32 = row.get("id");
-            let status: String = row.get("status");
-            let start_time: SystemTime = row.get("start_time");
-            let start_time = DateTime::from(start_time);
-            let update_time: SystemTime = row.get("update_time");
-            let update_time = DateTime::from(update_time);
-            let postgres::types::Json(data) = row.get("data");
-            InvocationRow { id, status, start_time, update_time, data }
-        }).collect();
-        rows.sort_by_key(|r| if r.status == "RUN" { (2, r.start_time) } else { (1, r.update_time) });
-        rows.reverse();
+        let rows = data::invocations(&mut *crate::store::open());
         let s = InvocationsTemplate { rows }.render().unwrap();
         return resp.code("200 OK").body(s);
     }
 
     if let Ok(id) = req.path.parse::<i32>() {
-        let client = &mut state.lock().unwrap().client;
-        let raw_row = client.query_one("
-        SELECT id, status, start_time, update_time, data
-        FROM invocations
-        WHERE id = $1", &[&id]).unwrap();
-        let status: String = raw_row.get("status");
-        let start_time: SystemTime = raw_row.get("start_time");
-        let start_time = DateTime::from(start_time);
-        let update_time: SystemTime = raw_row.get("update_time");
-        let update_time = DateTime::from(update_time);
-        let postgres::types::Json(data) = raw_row.get("data");
-        let row = InvocationRow { id, status, start_time, update_time, data };
-
+        let Some(row) = data::invocation(&mut *crate::store::open(), id) else {
+            return resp.code("404 Not Found").body(format!("no invocation {}", id));
+        };
         let formatted_data = serde_json::to_string_pretty(&row.data).unwrap();
         let s = InvocationTemplate { row, formatted_data }.render().unwrap();
         return resp.code("200 OK").body(s);
@@ -63,7 +36,14 @@ pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: Respo
                 {{ row.update_time.format("%d %H:%M:%S").to_string() }}
             {% endif %}
         </td>
-        <td>{{ row.status }}</td>
+        <td>
+            {% match row.data.panic_message %}
+            {% when Some with (msg) %}
+                <span title="{{ msg }}" style="color: red">{{ row.status }}</span>
+            {% when None %}
+                {{ row.status }}
+            {% endmatch %}
+        </td>
         <td>{{ row.data|render_invocation_ref(row.id)|safe }}</td>
     </tr>
 {% endfor %}
@@ -71,7 +51,7 @@ pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: Respo
 {% endblock %}
 "#)]
 struct InvocationsTemplate {
-    rows: Vec<InvocationRow>,
+    rows: Vec<InvocationView>,
 }
 
 #[derive(Template)]
@@ -83,6 +63,11 @@ struct InvocationsTemplate {
 <p>Started: {{ row.start_time.format("%d %H:%M:%S").to_string() }}</p>
 <p>Updated: {{ row.update_time.format("%d %H:%M:%S").to_string() }}</p>
 <p>Status: {{ row.status }}</p>
+{% match row.data.panic_message %}
+{% when Some with (msg) %}
+<pre style="color: red">{{ msg }}</pre>
+{% when None %}
+{% endmatch %}
 <pre>{{ formatted_data }}</pre>
 
 <hr>
@@ -92,18 +77,10 @@ struct InvocationsTemplate {
 {% endblock %}
 "#)]
 struct InvocationTemplate {
-    row: InvocationRow,
+    row: InvocationView,
     formatted_data: String,
 }
 
-struct InvocationRow {
-    id: i32,
-    status: String,
-    start_time: DateTime,
-    update_time: DateTime,
-    data: Invocation,
-}
-
 pub mod filters {
     use super::*;
 
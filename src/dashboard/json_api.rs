@@ -0,0 +1,66 @@
+// Read-only JSON views of the dashboard data, for scripts and notebooks.
+// See docs/dashboard.md for the list of endpoints.
+
+use super::data::{self, SolutionFilter};
+use super::dev_server::{Request, ResponseBuilder, HandlerResult};
+
+fn json(resp: ResponseBuilder, value: &impl serde::Serialize) -> HandlerResult {
+    resp.code("200 OK")
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string_pretty(value).unwrap())
+}
+
+pub fn handler(req: Request, resp: ResponseBuilder) -> HandlerResult {
+    let mut store = crate::store::open();
+
+    if req.path == "solutions" {
+        return match SolutionFilter::from_query(req.query_args) {
+            Ok(filter) => json(resp, &data::solutions(&mut *store, &filter)),
+            Err(e) => resp.code("400 Bad Request").body(e),
+        };
+    }
+
+    if let Some(id) = req.path.strip_prefix("solutions/") {
+        let (id, raw) = match id.strip_suffix("/data") {
+            Some(id) => (id, true),
+            None => (id, false),
+        };
+        let Ok(id) = id.parse::<i32>() else {
+            return resp.code("400 Bad Request").body(format!("solution id should be a number, got {:?}", id));
+        };
+        let Some(solution) = data::solution(&mut *store, id) else {
+            return resp.code("404 Not Found").body(format!("no solution {}", id));
+        };
+        if raw {
+            return resp.code("200 OK")
+                .header("Content-Type", "text/plain")
+                .body(store.solution_data(id));
+        }
+        return json(resp, &solution);
+    }
+
+    if req.path == "leaderboard" {
+        let valid_only = req.query_args.remove("valid_only").is_some_and(|x| x == "true");
+        return json(resp, &data::leaderboard(&mut *store, valid_only));
+    }
+
+    if req.path == "invocations" {
+        return json(resp, &data::invocations(&mut *store));
+    }
+
+    if let Some(id) = req.path.strip_prefix("invocations/") {
+        let Ok(id) = id.parse::<i32>() else {
+            return resp.code("400 Bad Request").body(format!("invocation id should be a number, got {:?}", id));
+        };
+        return match data::invocation(&mut *store, id) {
+            Some(inv) => json(resp, &inv),
+            None => resp.code("404 Not Found").body(format!("no invocation {}", id)),
+        };
+    }
+
+    if req.path == "submissions" {
+        return json(resp, &data::submissions(&mut *store));
+    }
+
+    resp.code("404 Not Found").body("not found")
+}
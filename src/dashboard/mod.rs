@@ -1,47 +1,63 @@
-mod dev_server;
+pub mod dev_server;
 mod static_files;
+mod compare;
+mod data;
 mod examples;
 mod invocations;
+mod json_api;
+mod problems;
+mod render;
 mod solutions;
+mod steps;
 mod submissions;
 
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use crate::util::project_root;
 use dev_server::Request;
 
+// For caches shared between requests.
+// A panic in one request shouldn't break the pages for all the others.
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 crate::entry_point!("dashboard", dashboard);
 fn dashboard() {
-    let state = State {
-        client: crate::db::create_client(),
-    };
-    let state = std::sync::Mutex::new(state);
-
     let listener = std::net::TcpListener::bind("127.0.0.1:8000").unwrap();
     eprintln!("serving at http://127.0.0.1:8000 ...");
     dev_server::serve_forever(listener, |req, resp| {
+        if let Some(path) = req.path.strip_prefix("/api/") {
+            return json_api::handler(Request { path, ..req }, resp);
+        }
+
         if let Some(path) = req.path.strip_prefix("/example/") {
             return examples::handler(Request { path, ..req }, resp);
         }
 
         if let Some(path) = req.path.strip_prefix("/invocation/") {
-            return invocations::handler(&state, Request { path, ..req }, resp);
+            return invocations::handler(Request { path, ..req }, resp);
+        }
+
+        if let Some(path) = req.path.strip_prefix("/problem/") {
+            return problems::handler(Request { path, ..req }, resp);
         }
 
         if let Some(path) = req.path.strip_prefix("/solution/") {
-            return solutions::handler(&state, Request { path, ..req }, resp);
+            return solutions::handler(Request { path, ..req }, resp);
         }
 
         if let Some(path) = req.path.strip_prefix("/submission/") {
-            return submissions::handler(&state, Request { path, ..req }, resp);
+            return submissions::handler(Request { path, ..req }, resp);
+        }
+
+        if req.path == "/compare" {
+            return compare::handler(req, resp);
         }
 
         if req.path == "/" {
-            return resp.code("303 See Other").header("Location", "/example/hello/world").body("");
+            return resp.code("303 See Other").header("Location", "/problem/").body("");
         }
 
         static_files::static_handler(&project_root(), req, resp)
     });
 }
-
-pub struct State {
-    client: postgres::Client,
-}
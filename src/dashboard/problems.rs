@@ -0,0 +1,289 @@
+use std::sync::Mutex;
+use askama::Template;
+use fxhash::FxHashMap as HashMap;
+use once_cell::sync::Lazy;
+
+use crate::basic::*;
+use crate::store::SolutionRow;
+use crate::util::DateTime;
+
+use super::lock;
+use super::data::{self, SolutionFilter, SolutionView};
+use super::dev_server::{Request, ResponseBuilder, HandlerResult};
+use super::render::{error_heatmap, png_data_uri, render_solution};
+
+pub fn handler(req: Request, resp: ResponseBuilder) -> HandlerResult {
+    if req.path.is_empty() {
+        let mut store = crate::store::open();
+        let problems = data::leaderboard(&mut *store, false).into_iter()
+            .map(|e| ProblemSummary {
+                problem_id: e.problem_id,
+                score_estimate: e.score_estimate,
+                best: e.best.map(|s| BestSolution::new(&mut *store, s)),
+            })
+            .collect();
+        let s = ProblemsTemplate { problems }.render().unwrap();
+        return resp.code("200 OK").body(s);
+    }
+
+    if let Ok(problem_id) = req.path.parse::<i32>() {
+        let mut store = crate::store::open();
+        let problem = Problem::load(problem_id);
+        let solutions = store.solutions(Some(problem_id));
+        let history = score_history(&solutions);
+        let history_svg = history_svg(&history);
+        let mut solver_bests = data::solutions(&mut *store, &SolutionFilter {
+            problem_id: Some(problem_id),
+            by_solver: true,
+            ..Default::default()
+        }).solutions;
+        solver_bests.sort_by_key(|s| (s.score, s.timestamp));
+        let best = solver_bests.first().cloned().map(|s| BestSolution::new(&mut *store, s));
+        let s = ProblemTemplate {
+            problem_id,
+            target_uri: png_data_uri(&problem.target),
+            num_solutions: solutions.len(),
+            best,
+            history,
+            history_svg,
+            solver_bests,
+        }.render().unwrap();
+        return resp.code("200 OK").body(s);
+    }
+
+    resp.code("404 Not Found").body("not found")
+}
+
+// Solutions don't change, so their renders are kept between requests.
+static RENDERS: Lazy<Mutex<HashMap<i32, Renders>>> = Lazy::new(Default::default);
+const MAX_RENDERS: usize = 500;
+
+#[derive(Clone)]
+struct Renders {
+    render_uri: String,
+    heatmap_uri: String,
+}
+
+struct BestSolution {
+    row: SolutionView,
+    // error if the solution can't be replayed
+    renders: Result<Renders, String>,
+}
+
+impl BestSolution {
+    fn new(store: &mut dyn crate::store::Store, row: SolutionView) -> BestSolution {
+        let cached = lock(&RENDERS).get(&row.id).cloned();
+        let renders = match cached {
+            Some(r) => Ok(r),
+            None => {
+                let problem = Problem::load(row.problem_id);
+                render_solution(&problem, &store.solution_data(row.id)).map(|img| {
+                    let r = Renders {
+                        render_uri: png_data_uri(&img),
+                        heatmap_uri: png_data_uri(&error_heatmap(&img, &problem.target)),
+                    };
+                    let mut cache = lock(&RENDERS);
+                    if cache.len() >= MAX_RENDERS {
+                        cache.clear();
+                    }
+                    cache.insert(row.id, r.clone());
+                    r
+                })
+            }
+        };
+        BestSolution { row, renders }
+    }
+}
+
+struct ProblemSummary {
+    problem_id: i32,
+    score_estimate: Option<i64>,
+    best: Option<BestSolution>,
+}
+
+// Solutions that were better than everything before them.
+struct Improvement {
+    timestamp: DateTime,
+    score: i64,
+    solution_id: i32,
+    solver_name: String,
+}
+
+// solutions are ordered by timestamp.
+fn score_history(solutions: &[SolutionRow]) -> Vec<Improvement> {
+    let mut res: Vec<Improvement> = vec![];
+    for s in solutions {
+        if res.last().is_none_or(|last| s.score < last.score) {
+            res.push(Improvement {
+                timestamp: s.timestamp,
+                score: s.score,
+                solution_id: s.id,
+                solver_name: s.solver_name.clone(),
+            });
+        }
+    }
+    res
+}
+
+const SVG_WIDTH: f64 = 600.0;
+const SVG_HEIGHT: f64 = 150.0;
+
+// Best score over time as a step line, the last improvement extends to now.
+fn history_svg(history: &[Improvement]) -> String {
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        return String::new();
+    };
+    let t0 = first.timestamp.timestamp() as f64;
+    let t1 = (chrono::Utc::now().timestamp() as f64).max(t0 + 1.0);
+    let max_score = first.score as f64;
+    let min_score = (last.score as f64).min(max_score - 1.0);
+    let x = |t: DateTime| (t.timestamp() as f64 - t0) / (t1 - t0) * SVG_WIDTH;
+    // Going down as the score improves.
+    let y = |score: i64| (max_score - score as f64) / (max_score - min_score) * (SVG_HEIGHT - 10.0) + 5.0;
+    let mut path = format!("M 0 {:.1}", y(first.score));
+    for imp in &history[1..] {
+        path += &format!(" H {:.1} V {:.1}", x(imp.timestamp), y(imp.score));
+    }
+    path += &format!(" H {:.1}", SVG_WIDTH);
+    format!(r#"<svg width="{w}" height="{h}" style="border: 1px solid lightgrey">
+        <path d="{path}" fill="none" stroke="blue" stroke-width="2"/></svg>"#,
+        w = SVG_WIDTH, h = SVG_HEIGHT, path = path)
+}
+
+#[derive(Template)]
+#[template(ext = "html", source = r#"
+{% extends "base.html" %}
+{% block title %}pr/{% endblock %}
+{% block body %}
+<style>
+    img.thumb {
+        width: 100px;
+        image-rendering: pixelated;
+    }
+</style>
+<table>
+<thead>
+<tr>
+    <th>pr.</th>
+    <th>target</th>
+    <th>best</th>
+    <th>error</th>
+    <th>score</th>
+    <th title="estimated best possible score, a lower bound only for solutions without swaps">est.</th>
+    <th>solver</th>
+</tr>
+</thead>
+{% for p in problems %}
+<tr>
+    <td><a href="/problem/{{ p.problem_id }}">#{{ p.problem_id }}</a></td>
+    <td><img class="thumb" src="/data/problems/{{ p.problem_id }}.png"/></td>
+    {% if let Some(best) = p.best %}
+    {% match best.renders %}
+    {% when Ok with (r) %}
+    <td><img class="thumb" src="{{ r.render_uri }}"/></td>
+    <td><img class="thumb" src="{{ r.heatmap_uri }}"/></td>
+    {% when Err with (e) %}
+    <td colspan="2" style="color: red" title="{{ e }}">invalid</td>
+    {% endmatch %}
+    <td><a href="/solution/{{ best.row.id }}">{{ best.row.score }}</a></td>
+    {% else %}
+    <td></td>
+    <td></td>
+    <td>no solutions</td>
+    {% endif %}
+    <td>{% if let Some(est) = p.score_estimate %}{{ est }}{% endif %}</td>
+    <td>{% if let Some(best) = p.best %}{{ best.row.solver_name }}{% endif %}</td>
+</tr>
+{% endfor %}
+</table>
+{% endblock %}
+"#)]
+struct ProblemsTemplate {
+    problems: Vec<ProblemSummary>,
+}
+
+#[derive(Template)]
+#[template(ext = "html", source = r#"
+{% extends "base.html" %}
+{% block title %}pr/{{ problem_id }}{% endblock %}
+{% block body %}
+<style>
+    img.big {
+        width: 400px;
+        image-rendering: pixelated;
+    }
+</style>
+<h3>Problem #{{ problem_id }}</h3>
+<a href="/solution/?problem_id={{ problem_id }}&archive=true">all {{ num_solutions }} solutions</a> |
+<a href="/solution/lineage/{{ problem_id }}">lineage</a>
+<table>
+<tr>
+    <td>target<br><img class="big" src="{{ target_uri }}"/></td>
+    {% if let Some(best) = best %}
+    <td>best: <a href="/solution/{{ best.row.id }}">sol/{{ best.row.id }}</a>,
+        {{ best.row.score }} = {{ best.row.image_distance }} + {{ best.row.moves_cost }}
+        by {{ best.row.solver_name }}<br>
+    {% match best.renders %}
+    {% when Ok with (r) %}
+        <img class="big" src="{{ r.render_uri }}"/></td>
+    <td>error<br><img class="big" src="{{ r.heatmap_uri }}"/></td>
+    {% when Err with (e) %}
+        <span style="color: red">can't replay: {{ e }}</span></td>
+    {% endmatch %}
+    {% endif %}
+</tr>
+</table>
+
+<h4>Score history</h4>
+{{ history_svg|safe }}
+<table>
+<thead>
+<tr>
+    <th>timestamp</th>
+    <th>score</th>
+    <th>solution</th>
+    <th>solver</th>
+</tr>
+</thead>
+{% for imp in history %}
+<tr>
+    <td>{{ imp.timestamp.format("%d %H:%M:%S").to_string() }}</td>
+    <td>{{ imp.score }}</td>
+    <td><a href="/solution/{{ imp.solution_id }}">sol/{{ imp.solution_id }}</a></td>
+    <td>{{ imp.solver_name }}</td>
+</tr>
+{% endfor %}
+</table>
+
+<h4>Best by solver</h4>
+<table>
+<thead>
+<tr>
+    <th>solver</th>
+    <th>score</th>
+    <th>solution</th>
+    <th>solver args</th>
+    <th>timestamp</th>
+</tr>
+</thead>
+{% for s in solver_bests %}
+<tr>
+    <td>{{ s.solver_name }}</td>
+    <td>{{ s.score }}</td>
+    <td><a href="/solution/{{ s.id }}">sol/{{ s.id }}</a></td>
+    <td>{{ s.solver_args }}</td>
+    <td>{{ s.timestamp.format("%d %H:%M:%S").to_string() }}</td>
+</tr>
+{% endfor %}
+</table>
+{% endblock %}
+"#)]
+struct ProblemTemplate {
+    problem_id: i32,
+    target_uri: String,
+    num_solutions: usize,
+    best: Option<BestSolution>,
+    history: Vec<Improvement>,
+    history_svg: String,
+    solver_bests: Vec<SolutionView>,
+}
@@ -0,0 +1,95 @@
+// Images for dashboard pages, rendered server-side and embedded as data URIs.
+
+use crate::basic::*;
+use crate::image::Image;
+
+pub fn data_uri(data: &[u8], mime_type: &str) -> String {
+    let mut s = String::new();
+    s.push_str("data:");
+    s.push_str(mime_type);
+    s.push_str(";base64,");
+    s.push_str(&base64::encode(data));
+    s
+}
+
+pub fn png_data_uri(img: &Image) -> String {
+    data_uri(&img.to_png(), "image/png")
+}
+
+pub fn render_solution(problem: &Problem, isl: &str) -> Result<Image, String> {
+    let moves = Move::try_parse_many(isl)?;
+    let mut painter = PainterState::new(problem);
+    for m in &moves {
+        painter.try_apply_move(m).map_err(|e| e.to_string())?;
+    }
+    Ok(painter.render())
+}
+
+// Max distance between two colors.
+const MAX_DIST: f64 = 510.0;
+
+// White where the image matches the target, red where it's far off.
+pub fn error_heatmap(img: &Image, target: &Image) -> Image {
+    let mut res = Image::new(img.width, img.height, Color::default());
+    for y in 0..img.height {
+        for x in 0..img.width {
+            // sqrt to make small errors visible
+            let t = (img.get_pixel(x, y).dist(&target.get_pixel(x, y)) / MAX_DIST).sqrt();
+            let c = (255.0 * (1.0 - t)).round() as u8;
+            res.set_pixel(x, y, Color([255, c, c, 255]));
+        }
+    }
+    res
+}
+
+// Blue where `a` is closer to the target, orange where `b` is, white where they are equally close.
+// Also returns the number of pixels where each one is closer.
+pub fn closer_heatmap(a: &Image, b: &Image, target: &Image) -> (Image, usize, usize) {
+    let mut res = Image::new(a.width, a.height, Color::default());
+    let (mut a_closer, mut b_closer) = (0, 0);
+    for y in 0..a.height {
+        for x in 0..a.width {
+            let t = target.get_pixel(x, y);
+            let d = (a.get_pixel(x, y).dist(&t) - b.get_pixel(x, y).dist(&t)) / MAX_DIST;
+            let c = (255.0 * (1.0 - d.abs().sqrt())).round() as u8;
+            let color = if d < 0.0 {
+                a_closer += 1;
+                Color([c, c, 255, 255])
+            } else if d > 0.0 {
+                b_closer += 1;
+                Color([255, (c as u16 * 3 / 4 + 64) as u8, c, 255])
+            } else {
+                Color([255, 255, 255, 255])
+            };
+            res.set_pixel(x, y, color);
+        }
+    }
+    (res, a_closer, b_closer)
+}
+
+#[test]
+fn test_error_heatmap() {
+    let white = Color([255, 255, 255, 255]);
+    let target = Image::new(2, 1, white);
+    let mut img = target.clone();
+    img.set_pixel(1, 0, Color([0, 0, 0, 0]));
+    let h = error_heatmap(&img, &target);
+    assert_eq!(h.get_pixel(0, 0), white);
+    assert_eq!(h.get_pixel(1, 0), Color([255, 0, 0, 255]));
+}
+
+#[test]
+fn test_closer_heatmap() {
+    let white = Color([255, 255, 255, 255]);
+    let transparent = Color([0, 0, 0, 0]);
+    let target = Image::new(3, 1, white);
+    let mut a = target.clone();
+    let mut b = target.clone();
+    a.set_pixel(1, 0, transparent);
+    b.set_pixel(2, 0, transparent);
+    let (h, a_closer, b_closer) = closer_heatmap(&a, &b, &target);
+    assert_eq!((a_closer, b_closer), (1, 1));
+    assert_eq!(h.get_pixel(0, 0), white);
+    assert_eq!(h.get_pixel(1, 0), Color([255, 64, 0, 255]));
+    assert_eq!(h.get_pixel(2, 0), Color([0, 0, 255, 255]));
+}
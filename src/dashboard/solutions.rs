@@ -1,153 +1,117 @@
 // use std::time::SystemTime;
 use fxhash::FxHashMap as HashMap;
 use askama::Template;
-use postgres::types::Json;
 use crate::{util::DateTime, invocation::Invocation};
 use crate::basic::*;
 use crate::basic::Move::*;
-use crate::util::project_path;
+use crate::image::Image;
 
+use super::data::{self, SolutionFilter, SolutionList, SolutionView, VerificationMark};
 use super::dev_server::{Request, ResponseBuilder, HandlerResult};
+use super::render::png_data_uri;
 // use crate::invocation::Invocation;
 // use crate::solution::Solution;
 // use crate::util::DateTime;
 
-pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: ResponseBuilder) -> HandlerResult {
-    if req.path.is_empty() {
-        let client = &mut state.lock().unwrap().client;
+pub(super) const MOVE_KINDS: [&str; 5] = ["color", "lcut", "pcut", "merge", "swap"];
 
-        let archive = req.query_args.remove("archive");
-        let problem_id: i32 = req.query_args.remove("problem_id").map_or(-1, |s| s.parse().unwrap());
+pub(super) fn move_kind(m: &Move) -> &'static str {
+    match m {
+        ColorMove { .. } => "color",
+        LCut { .. } => "lcut",
+        PCut { .. } => "pcut",
+        Merge { .. } => "merge",
+        Swap { .. } => "swap",
+    }
+}
 
-        let archive = match archive {
-            Some(x) => x == "true",
-            None => false
-        };
-        let opts = SolutionsOpts { archive };
-
-        let mut problem_id_to_rows: HashMap<i32, Vec<SolutionRow>> = HashMap::default();
-        let raw_rows = client.query("
-            SELECT
-                id,
-                problem_id,
-                data,
-                moves_cost,
-                image_distance,
-                solver,
-                solver_args,
-                invocation_id,
-                timestamp
-            FROM solutions
-            WHERE $1 = -1 OR $1 = problem_id
-            ORDER BY timestamp
-        ", &[&problem_id]).unwrap();
-        for row in raw_rows {
-            let id: i32 = row.get("id");
-            let problem_id: i32 = row.get("problem_id");
-            // let data: String = row.get("data");
-            let moves_cost: i64 = row.get("moves_cost");
-            let image_distance: i64 = row.get("image_distance");
-            let score: i64 = moves_cost + image_distance;
-            let solver_name: String = row.get("solver");
-            let solver_args: Json<serde_json::Value> = row.get("solver_args");
-            let invocation_id: i32 = row.get("invocation_id");
-            let timestamp: DateTime = row.get("timestamp");
-
-            let solver_args = serde_json::to_string(&solver_args.0).unwrap();
-            let sr = SolutionRow {
-                id,
-                problem_id,
-                /* data, */
-                moves_cost,
-                image_distance,
-                score,
-                solver_name,
-                solver_args,
-                invocation_id,
-                timestamp,
-                best: false,
-            };
-            problem_id_to_rows.entry(problem_id).or_default().push(sr);
-        }
-        if !archive {
-            for rows in problem_id_to_rows.values_mut() {
-                let best = rows.iter().min_by_key(|r| (r.score, r.timestamp)).unwrap();
-                *rows = vec![best.clone()];
-            }
-        } else {
-            for rows in problem_id_to_rows.values_mut() {
-                let score = rows.iter().map(|r| r.score).min().unwrap();
-                for row in rows {
-                    row.best = row.score == score;
-                }
-            }
-        }
+pub(super) struct Replay {
+    pub img: Image,
+    pub cost: i64,
+    pub cost_breakdown: HashMap<&'static str, i64>,
+    pub move_counts: HashMap<&'static str, usize>,
+    // sizes of the blocks left on the canvas
+    pub block_sizes: Vec<i32>,
+}
 
-        let mut problem_id_to_rows: Vec<_> = problem_id_to_rows.into_iter().collect();
-        problem_id_to_rows.sort_by_key(|(problem_id, _)| *problem_id);
-        let rows: Vec<SolutionRow> = problem_id_to_rows.into_iter().flat_map(|q| q.1.into_iter()).collect();
+pub(super) fn replay(problem: &Problem, moves: &[Move]) -> Result<Replay, String> {
+    let mut painter = PainterState::new(problem);
+    let mut cost_breakdown: HashMap<&'static str, i64> = HashMap::default();
+    let mut move_counts: HashMap<&'static str, usize> = HashMap::default();
+    for m in moves {
+        let c = painter.try_apply_move(m).map_err(|e| e.to_string())?.cost;
+        *cost_breakdown.entry(move_kind(m)).or_default() += c;
+        *move_counts.entry(move_kind(m)).or_default() += 1;
+    }
+    Ok(Replay {
+        img: painter.render(),
+        cost: painter.cost,
+        cost_breakdown,
+        move_counts,
+        block_sizes: painter.blocks.values().map(|b| b.shape.size()).collect(),
+    })
+}
+
+pub fn handler(req: Request, resp: ResponseBuilder) -> HandlerResult {
+    if req.path.is_empty() {
+        let opts = match SolutionFilter::from_query(req.query_args) {
+            Ok(opts) => opts,
+            Err(e) => return resp.code("400 Bad Request").body(e),
+        };
+        let SolutionList { total_score, solutions: rows } = data::solutions(&mut *crate::store::open(), &opts);
         let s = SolutionsTemplate {
-            total_score: rows.iter().map(|r| r.score).sum(),
+            total_score,
+            problem_id: opts.problem_id,
             opts,
             rows,
         }.render().unwrap();
         return resp.code("200 OK").body(s);
     }
 
-    if let Ok(id) = req.path.parse::<i32>() {
-        let client = &mut state.lock().unwrap().client;
-        let raw_row = client.query_one("SELECT problem_id, data, moves_cost, image_distance, invocation_id FROM solutions WHERE id = $1", &[&id]).unwrap();
-        let problem_id: i32 = raw_row.get("problem_id");
-        let moves_cost: i64 = raw_row.get("moves_cost");
-        let image_dist: i64 = raw_row.get("image_distance");
-        let invocation_id: i32 = raw_row.get("invocation_id");
+    if let Some(problem_id) = req.path.strip_prefix("lineage/") {
+        let problem_id: i32 = problem_id.parse().unwrap();
+        let rows = crate::store::open().solutions(Some(problem_id));
+        let nodes = lineage_nodes(&rows);
+        let derivations = derivation_stats(&nodes);
+        let s = LineageTemplate { problem_id, nodes, derivations }.render().unwrap();
+        return resp.code("200 OK").body(s);
+    }
 
-        let inv_row = client.query_one("SELECT data FROM invocations WHERE id = $1", &[&invocation_id]).unwrap();
-        let postgres::types::Json(inv_data) = inv_row.get("data");
+    if let Some(Ok(id)) = req.path.strip_suffix("/step").map(str::parse) {
+        return super::steps::handler(id, req, resp);
+    }
 
-        let data: String = raw_row.get("data");
-        let moves = Move::parse_many(&data);
+    if let Ok(id) = req.path.parse::<i32>() {
+        let mut store = crate::store::open();
+        let Some(row) = data::solution(&mut *store, id) else {
+            return resp.code("404 Not Found").body(format!("no solution {}", id));
+        };
+        let parent_id = row.parent_id;
+        let derivation = row.derivation;
+        let problem_id = row.problem_id;
+        let moves_cost = row.moves_cost;
+        let image_dist = row.image_distance;
+        let invocation_id = row.invocation_id;
 
-        let problem = Problem::load(problem_id);
-        let mut painter = PainterState::new(&problem);
-
-        let mut cost_breakdown: HashMap<&'static str, i64> = HashMap::default();
-        for m in &moves {
-            let c = painter.apply_move(m).cost;
-            match m {
-                ColorMove { .. } => {
-                    *cost_breakdown.entry("color").or_default() += c;
-                }
-                LCut { .. } => {
-                    *cost_breakdown.entry("lcut").or_default() += c;
-                }
-                PCut { .. } => {
-                    *cost_breakdown.entry("pcut").or_default() += c;
-                }
-                Merge { .. } => {
-                    *cost_breakdown.entry("merge").or_default() += c;
-                }
-                Swap { .. } => {
-                    *cost_breakdown.entry("swap").or_default() += c;
-                }
-            }
-        }
-        // assert_eq!(painter.cost, moves_cost);
+        let inv_data = store.invocation(invocation_id).data;
 
-        let img = painter.render();
+        let data = store.solution_data(id);
+        let problem = Problem::load(problem_id);
+        let replay = match Move::try_parse_many(&data).and_then(|moves| replay(&problem, &moves)) {
+            Ok(replay) => replay,
+            Err(e) => return resp.code("422 Unprocessable Entity").body(format!("Can't replay the solution: {}", e)),
+        };
+        let cost_breakdown = replay.cost_breakdown;
+        let img = replay.img;
 
         let dist = image_distance(&problem.target, &img).round() as i64;
-        // assert_eq!(dist, image_dist);
-        if painter.cost != moves_cost || dist != image_dist {
+        if replay.cost != moves_cost || dist != image_dist {
             let s = format!("Our current scorer ({} + {}) disagrees with the scores recorded in the DB ({} + {}).",
-                painter.cost, dist, moves_cost, image_dist);
+                replay.cost, dist, moves_cost, image_dist);
             return resp.code("200 OK").body(s);
         }
 
-        let path = project_path("cache/tmp.png");
-        img.save(&path);
-        let png = std::fs::read(&path).unwrap();
-        let img_data_uri = data_uri(&png, "image/png");
+        let img_data_uri = png_data_uri(&img);
 
         let s = SolutionTemplate {
             id,
@@ -159,6 +123,8 @@ pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: Respo
             img_data_uri,
             invocation_id,
             inv_data,
+            parent_id,
+            derivation,
         }.render().unwrap();
         return resp.code("200 OK").body(s);
     }
@@ -166,8 +132,11 @@ pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: Respo
     if let Some(path) = req.path.strip_prefix("submit/") {
         assert_eq!(req.method, "POST");
         let solution_id: i32 = path.parse().unwrap();
-        let client = &mut state.lock().unwrap().client;
-        let submission_id = crate::api::submit_solution(client, solution_id);
+        let mut store = crate::store::open();
+        let submission_id = match crate::api::submit_solution(&crate::api::Conf::load(), &mut *store, solution_id) {
+            Ok(submission_id) => submission_id,
+            Err(e) => return resp.code("502 Bad Gateway").body(format!("can't submit: {}", e)),
+        };
 
         std::thread::sleep(std::time::Duration::from_secs_f32(1.0));
         // dunno why, for some reason the submission
@@ -179,34 +148,6 @@ pub fn handler(state: &std::sync::Mutex<super::State>, req: Request, resp: Respo
     resp.code("404 Not Found").body("not found")
 }
 
-fn data_uri(data: &[u8], mime_type: &str) -> String {
-    let mut s = String::new();
-    s.push_str("data:");
-    s.push_str(mime_type);
-    s.push_str(";base64,");
-    s.push_str(&base64::encode(data));
-    s
-}
-
-#[derive(Clone)]
-struct SolutionRow {
-    id: i32,
-    problem_id: i32,
-    //data: String,
-    moves_cost: i64,
-    image_distance: i64,
-    score: i64,
-    solver_name: String,
-    solver_args: String,
-    invocation_id: i32,
-    timestamp: DateTime,
-    best: bool,
-}
-
-struct SolutionsOpts {
-    archive: bool,
-}
-
 #[derive(Template)]
 #[template(ext = "html", source = r#"
 {% extends "base.html" %}
@@ -220,12 +161,38 @@ struct SolutionsOpts {
 </style>
 
 <a href="?">best</a> |
-<a href="?archive={{ !opts.archive }}">archive (all)</a>
+<a href="?top=5">top 5</a> |
+<a href="?by_solver=true">best by solver</a> |
+<a href="?archive={{ !opts.archive }}">archive (all)</a> |
+<a href="?valid_only={{ !opts.valid_only }}">
+{% if opts.valid_only %}include invalid{% else %}valid only{% endif %}
+</a>
+{% if let Some(problem_id) = problem_id %}
+| <a href="/solution/lineage/{{ problem_id }}">lineage of #{{ problem_id }}</a>
+{% endif %}
 <hr />
 
-{% if !opts.archive %}
-<p>Total score: {{ total_score }}</p>
+<p>
+{% if opts.archive %}
+    All solutions.
+{% else if opts.by_solver %}
+    Best solution of each solver.
+{% else if let Some(n) = opts.top %}
+    Top {{ n }} solutions.
+{% else %}
+    Best solutions.
+{% endif %}
+{% if opts.valid_only %}
+    Solutions that failed verification are excluded.
 {% endif %}
+{% if let Some(solver) = opts.solver %}
+    Only by {{ solver }}.
+{% endif %}
+{% if let Some(invocation_id) = opts.invocation_id %}
+    Only from <a href="/invocation/{{ invocation_id }}">inv/{{ invocation_id }}</a>.
+{% endif %}
+Total score of the best: {{ total_score }}
+</p>
 
 <table>
 <thead>
@@ -237,9 +204,11 @@ struct SolutionsOpts {
     <th>image dist.</th>
     <th></th>
     <th>moves cost</th>
+    <th title="score / estimated best possible score, can be below 1 for solutions with swaps">score / est.</th>
     <th></th>
     <th>solver name</th>
     <th>solver args</th>
+    <th>verified</th>
     <th>inv.<th>
 </tr>
 </thead>
@@ -253,9 +222,20 @@ struct SolutionsOpts {
         <td style="text-align: right">{{ row.image_distance }}</td>
         <td>+</td>
         <td>{{ row.moves_cost }}</td>
+        <td style="text-align: right" title="estimate {{ row.score_estimate.unwrap_or(0) }}">{{ row.gap() }}</td>
         <td><a href="/solution/{{ row.id }}">sol/{{ row.id }}</a></td>
         <td>{{ row.solver_name }}</td>
         <td>{{ row.solver_args }}</td>
+        {% match row.verification %}
+        {% when VerificationMark::Unverified %}
+        <td></td>
+        {% when VerificationMark::Ok %}
+        <td>ok</td>
+        {% when VerificationMark::Invalid with (error) %}
+        <td style="color: red" title="{{ error }}">invalid</td>
+        {% when VerificationMark::Changed with (details) %}
+        <td style="color: orange" title="{{ details }}">changed</td>
+        {% endmatch %}
         <td><a href="/invocation/{{ row.invocation_id }}">inv/{{ row.invocation_id }}</a></td>
     </tr>
 {% endfor %}
@@ -263,9 +243,150 @@ struct SolutionsOpts {
 {% endblock %}
 "#)]
 struct SolutionsTemplate {
-    opts: SolutionsOpts,
+    opts: SolutionFilter,
     total_score: i64,
-    rows: Vec<SolutionRow>,
+    problem_id: Option<i32>,
+    rows: Vec<SolutionView>,
+}
+
+// Solution in the lineage tree, in depth-first order.
+struct LineageNode {
+    id: i32,
+    depth: usize,
+    score: i64,
+    gain: Option<i64>,  // parent score - score
+    solver_name: String,
+    derivation: String,
+    timestamp: DateTime,
+}
+
+impl LineageNode {
+    fn improved(&self) -> bool {
+        self.gain.is_some_and(|g| g > 0)
+    }
+
+    fn score_change(&self) -> String {
+        self.gain.map(|g| format!("{:+}", -g)).unwrap_or_default()
+    }
+}
+
+// Only solutions that are derived from something or have something derived from them.
+fn lineage_nodes(rows: &[crate::store::SolutionRow]) -> Vec<LineageNode> {
+    let by_id: HashMap<i32, &crate::store::SolutionRow> = rows.iter().map(|r| (r.id, r)).collect();
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::default();
+    for r in rows {
+        if let Some(parent_id) = r.parent_id {
+            children.entry(parent_id).or_default().push(r.id);
+        }
+    }
+    // rows are ordered by timestamp, so children are too
+    let roots = rows.iter()
+        .filter(|r| r.parent_id.is_none_or(|p| !by_id.contains_key(&p)))
+        .filter(|r| children.contains_key(&r.id))
+        .map(|r| r.id);
+
+    let mut nodes = vec![];
+    let mut stack: Vec<(i32, usize)> = roots.rev().map(|id| (id, 0)).collect();
+    while let Some((id, depth)) = stack.pop() {
+        let r = by_id[&id];
+        let gain = r.parent_id.and_then(|p| by_id.get(&p)).map(|p| p.score - r.score);
+        nodes.push(LineageNode {
+            id,
+            depth,
+            score: r.score,
+            gain,
+            solver_name: r.solver_name.clone(),
+            derivation: r.derivation.clone().unwrap_or_default(),
+            timestamp: r.timestamp,
+        });
+        if let Some(ch) = children.get(&id) {
+            stack.extend(ch.iter().rev().map(|&c| (c, depth + 1)));
+        }
+    }
+    nodes
+}
+
+struct DerivationStats {
+    derivation: String,
+    count: usize,
+    improved: usize,
+    total_gain: i64,
+}
+
+fn derivation_stats(nodes: &[LineageNode]) -> Vec<DerivationStats> {
+    let mut stats: HashMap<&str, DerivationStats> = HashMap::default();
+    for n in nodes {
+        let Some(gain) = n.gain else { continue };
+        let s = stats.entry(&n.derivation).or_insert_with(|| DerivationStats {
+            derivation: n.derivation.clone(),
+            count: 0,
+            improved: 0,
+            total_gain: 0,
+        });
+        s.count += 1;
+        s.improved += (gain > 0) as usize;
+        s.total_gain += gain;
+    }
+    let mut stats: Vec<DerivationStats> = stats.into_values().collect();
+    stats.sort_by_key(|s| -s.total_gain);
+    stats
+}
+
+#[derive(Template)]
+#[template(ext = "html", source = r#"
+{% extends "base.html" %}
+{% block title %}lineage #{{ problem_id }}{% endblock %}
+{% block body %}
+<a href="/solution/?problem_id={{ problem_id }}&archive=true">all solutions for #{{ problem_id }}</a>
+<hr />
+
+<table>
+<thead>
+<tr>
+    <th>derivation</th>
+    <th>count</th>
+    <th>improved</th>
+    <th>total gain</th>
+</tr>
+</thead>
+{% for s in derivations %}
+    <tr>
+        <td>{{ s.derivation }}</td>
+        <td style="text-align: right">{{ s.count }}</td>
+        <td style="text-align: right">{{ s.improved }}</td>
+        <td style="text-align: right">{{ s.total_gain }}</td>
+    </tr>
+{% endfor %}
+</table>
+<hr />
+
+<table>
+{% for n in nodes %}
+    <tr>
+        <td style="padding-left: {{ n.depth * 2 }}em">
+            <a href="/solution/{{ n.id }}">sol/{{ n.id }}</a>
+            {% if !n.derivation.is_empty() %} &larr; {{ n.derivation }}{% endif %}
+        </td>
+        <td>{{ n.solver_name }}</td>
+        <td style="text-align: right">{{ n.score }}</td>
+        <td style="text-align: right">
+            {% if n.gain.is_some() %}
+            <span style="color: {% if n.improved() %}green{% else %}red{% endif %}">{{ n.score_change() }}</span>
+            {% endif %}
+        </td>
+        <td>{{ n.timestamp.format("%d %H:%M:%S").to_string() }}</td>
+    </tr>
+{% endfor %}
+</table>
+{% if nodes.is_empty() %}
+<p>No derived solutions for this problem.</p>
+{% endif %}
+{% endblock %}
+"#)]
+struct LineageTemplate {
+    problem_id: i32,
+    nodes: Vec<LineageNode>,
+    derivations: Vec<DerivationStats>,
 }
 
 #[derive(Template)]
@@ -274,9 +395,20 @@ struct SolutionsTemplate {
 {% block title %}sol/{{ id }}{% endblock %}
 {% block body %}
 <p>{{ inv_data|render_invocation_ref(invocation_id)|safe }}</p>
+{% if let Some(parent_id) = parent_id %}
+<p>
+    Derived from <a href="/solution/{{ parent_id }}">sol/{{ parent_id }}</a>
+    ({{ derivation.as_deref().unwrap_or("") }}),
+    see <a href="/solution/lineage/{{ problem_id }}">lineage</a>
+</p>
+{% endif %}
 <p>Score: {{ moves_cost + image_distance }} = {{ image_distance }} + {{ moves_cost }}</p>
 <p>Move cost breakdown: {{ "{:?}"|format(cost_breakdown) }} </p>
-<p><a id="run_in_interpreter">Run in visualizer</a></p>
+<p><a id="run_in_interpreter">Run in visualizer</a>, <a href="/solution/{{ id }}/step">step through</a></p>
+<form action="/compare">
+<input type="hidden" name="a" value="{{ id }}">
+Compare with sol/<input name="b" size="6"> <input type="submit" value="Compare">
+</form>
 <form method="POST" action="/solution/submit/{{ id }}">
 <input type="submit" value="Submit this solution">
 </form>
@@ -307,6 +439,8 @@ struct SolutionTemplate {
     img_data_uri: String,
     invocation_id: i32,
     inv_data: Invocation,
+    parent_id: Option<i32>,
+    derivation: Option<String>,
 }
 
 mod filters {
@@ -0,0 +1,305 @@
+// Step-through view of a solution: the canvas after any prefix of its moves.
+
+use std::sync::Mutex;
+use std::fmt::Write;
+use askama::Template;
+use fxhash::FxHashMap as HashMap;
+use once_cell::sync::Lazy;
+
+use crate::basic::*;
+use crate::basic::Move::*;
+
+use super::lock;
+use super::dev_server::{Request, ResponseBuilder, HandlerResult};
+use super::render::png_data_uri;
+
+// Problems are leaked so that cached painters can borrow them across requests.
+static PROBLEMS: Lazy<Mutex<HashMap<i32, &'static Problem>>> = Lazy::new(Default::default);
+
+// Painters are kept between requests, so stepping to an adjacent move
+// only applies or rolls back the moves in between.
+static STEPPERS: Lazy<Mutex<HashMap<i32, Stepper>>> = Lazy::new(Default::default);
+const MAX_STEPPERS: usize = 8;
+
+fn leaked_problem(problem_id: i32) -> &'static Problem {
+    if let Some(&p) = lock(&PROBLEMS).get(&problem_id) {
+        return p;
+    }
+    let problem = Problem::load(problem_id);
+    lock(&PROBLEMS).entry(problem_id).or_insert_with(|| Box::leak(Box::new(problem)))
+}
+
+struct Stepper {
+    problem_id: i32,
+    painter: PainterState<'static>,
+    moves: Vec<Move>,
+    costs: Vec<i64>,
+    touched: Vec<Vec<BlockId>>,
+    // index of the first move that can't be applied
+    error: Option<(usize, String)>,
+}
+
+impl Stepper {
+    fn new(problem_id: i32, moves: Vec<Move>) -> Stepper {
+        let mut painter = PainterState::new(leaked_problem(problem_id));
+        let mut costs = vec![];
+        let mut touched = vec![];
+        let mut error = None;
+        for (i, m) in moves.iter().enumerate() {
+            match painter.try_apply_move(m) {
+                Ok(res) => {
+                    costs.push(res.cost);
+                    touched.push(touched_blocks(m, res.new_block_ids));
+                }
+                Err(e) => {
+                    error = Some((i, e.to_string()));
+                    break;
+                }
+            }
+        }
+        Stepper { problem_id, painter, moves, costs, touched, error }
+    }
+
+    // Number of moves that can be applied.
+    fn valid_len(&self) -> usize {
+        self.costs.len()
+    }
+
+    fn seek(&mut self, n: usize) {
+        assert!(n <= self.valid_len());
+        while self.painter.moves.len() > n {
+            self.painter.rollback_move();
+        }
+        while self.painter.moves.len() < n {
+            let m = self.moves[self.painter.moves.len()].clone();
+            self.painter.apply_move(&m);
+        }
+    }
+}
+
+fn touched_blocks(m: &Move, new_block_ids: Vec<BlockId>) -> Vec<BlockId> {
+    match m {
+        ColorMove { block_id, .. } => vec![block_id.clone()],
+        Swap { block_id1, block_id2 } => vec![block_id1.clone(), block_id2.clone()],
+        PCut { .. } | LCut { .. } | Merge { .. } => new_block_ids,
+    }
+}
+
+// Canvas with block outlines and IDs on top, blocks touched by the last move highlighted.
+fn frame_svg(blocks: &[(BlockId, Shape)], img: &crate::image::Image, touched: &[BlockId]) -> String {
+    let (w, h) = (img.width, img.height);
+    let mut s = String::new();
+    writeln!(s, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+        2 * w, 2 * h, w, h).unwrap();
+    writeln!(s, r#"<image width="{}" height="{}" style="image-rendering: pixelated" href="{}"/>"#,
+        w, h, png_data_uri(img)).unwrap();
+    let mut blocks: Vec<_> = blocks.iter().collect();
+    blocks.sort_by_key(|(id, _)| touched.contains(id));
+    for &(ref id, sh) in blocks {
+        let stroke = if touched.contains(id) { "#f0f" } else { "#0008" };
+        // canvas y axis points up
+        writeln!(s, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-width="0.5"/>"#,
+            sh.x1, h - sh.y2, sh.width(), sh.height(), stroke).unwrap();
+        let label = id.to_string();
+        let font_size = (sh.height() as f64 * 0.6).min(8.0);
+        if font_size >= 3.0 && sh.width() as f64 >= font_size * 0.6 * label.len() as f64 {
+            writeln!(s, r#"<text x="{}" y="{}" font-size="{:.1}" text-anchor="middle" dominant-baseline="middle" fill="black" stroke="white" stroke-width="{:.1}" paint-order="stroke">{}</text>"#,
+                sh.x1 as f64 + sh.width() as f64 / 2.0,
+                (h - sh.y2) as f64 + sh.height() as f64 / 2.0,
+                font_size, font_size / 4.0, label).unwrap();
+        }
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+// Handles "/solution/{id}/step?n=N".
+pub fn handler(solution_id: i32, req: Request, resp: ResponseBuilder) -> HandlerResult {
+    let n: Option<usize> = match req.query_args.remove("n").map(str::parse).transpose() {
+        Ok(n) => n,
+        Err(_) => return resp.code("400 Bad Request").body("n should be a move number"),
+    };
+
+    let mut steppers = lock(&STEPPERS);
+    if !steppers.contains_key(&solution_id) {
+        // Don't hold the lock while talking to the DB and replaying.
+        drop(steppers);
+        let mut store = crate::store::open();
+        let Some(row) = store.find_solution(solution_id) else {
+            return resp.code("404 Not Found").body("not found");
+        };
+        let moves = match Move::try_parse_many(&store.solution_data(solution_id)) {
+            Ok(moves) => moves,
+            Err(e) => return resp.code("422 Unprocessable Entity").body(format!("can't parse solution: {}", e)),
+        };
+        let stepper = Stepper::new(row.problem_id, moves);
+        steppers = lock(&STEPPERS);
+        if steppers.len() >= MAX_STEPPERS {
+            steppers.clear();
+        }
+        steppers.entry(solution_id).or_insert(stepper);
+    }
+    let stepper = steppers.get_mut(&solution_id).unwrap();
+
+    let n = n.unwrap_or(stepper.valid_len()).min(stepper.valid_len());
+    stepper.seek(n);
+    let img = stepper.painter.render();
+    let problem_id = stepper.problem_id;
+    let cost = stepper.painter.cost;
+    let blocks: Vec<(BlockId, Shape)> = stepper.painter.blocks.iter()
+        .map(|(id, b)| (id.clone(), b.shape)).collect();
+    let touched = if n > 0 { stepper.touched[n - 1].clone() } else { vec![] };
+    let num_moves = stepper.moves.len();
+    let valid_len = stepper.valid_len();
+    let error = stepper.error.clone();
+
+    let mut running_cost = 0;
+    let moves = stepper.moves.iter().enumerate().map(|(i, m)| {
+        let c = stepper.costs.get(i).cloned();
+        running_cost += c.unwrap_or(0);
+        StepMove {
+            n: i + 1,
+            text: m.to_string(),
+            cost: c,
+            running_cost,
+        }
+    }).collect();
+    drop(steppers);
+
+    let target = &leaked_problem(problem_id).target;
+    let dist = image_distance(target, &img).round() as i64;
+
+    let s = StepTemplate {
+        id: solution_id,
+        problem_id,
+        n,
+        prev: n.saturating_sub(1),
+        next: (n + 1).min(valid_len),
+        num_moves,
+        valid_len,
+        cost,
+        dist,
+        num_blocks: blocks.len(),
+        frame_svg: frame_svg(&blocks, &img, &touched),
+        error,
+        moves,
+    }.render().unwrap();
+    resp.code("200 OK").body(s)
+}
+
+struct StepMove {
+    n: usize,
+    text: String,
+    cost: Option<i64>,
+    running_cost: i64,
+}
+
+#[derive(Template)]
+#[template(ext = "html", source = r#"
+{% extends "base.html" %}
+{% block title %}sol/{{ id }} step {{ n }}{% endblock %}
+{% block body %}
+<style>
+.steps { display: flex; gap: 1em; align-items: flex-start; }
+.moves { max-height: 800px; overflow-y: auto; font-family: monospace; }
+.moves tr.current { background: #ff0 !important; }
+</style>
+<p>
+    <a href="/solution/{{ id }}">sol/{{ id }}</a>
+    for <a href="/problem/{{ problem_id }}">problem {{ problem_id }}</a>
+</p>
+<p>
+    <a href="?n=0">|&lt;</a>
+    <a href="?n={{ prev }}">&lt;</a>
+    move {{ n }} / {{ num_moves }}
+    <a href="?n={{ next }}">&gt;</a>
+    <a href="?n={{ valid_len }}">&gt;|</a>
+    <small>(arrows: &plusmn;1, page up/down: &plusmn;10, home/end)</small>
+</p>
+<p>
+    Score: {{ cost + dist }} = {{ dist }} + {{ cost }}, {{ num_blocks }} blocks
+</p>
+{% if let Some((error_idx, error)) = error %}
+<p>Move {{ error_idx + 1 }} can't be applied: {{ error }}</p>
+{% endif %}
+<div class="steps">
+<div>{{ frame_svg|safe }}</div>
+<div><img src="/data/problems/{{ problem_id }}.png" width="800"/></div>
+<div class="moves">
+<table>
+<tr><th>#</th><th>move</th><th>cost</th><th>total</th></tr>
+{% for m in moves %}
+<tr id="move{{ m.n }}" {% if m.n == n %}class="current"{% endif %}>
+    <td><a href="?n={{ m.n }}">{{ m.n }}</a></td>
+    <td>{{ m.text }}</td>
+    {% if let Some(c) = m.cost %}
+    <td>{{ c }}</td><td>{{ m.running_cost }}</td>
+    {% else %}
+    <td></td><td></td>
+    {% endif %}
+</tr>
+{% endfor %}
+</table>
+</div>
+</div>
+<script>
+(function () {
+const n = {{ n }}, last = {{ valid_len }};
+const cur = document.getElementById(`move${n}`);
+if (cur) cur.scrollIntoView({block: "center"});
+document.addEventListener("keydown", e => {
+    const delta = {ArrowLeft: -1, ArrowRight: 1, PageUp: -10, PageDown: 10}[e.key];
+    let k = null;
+    if (delta !== undefined) k = Math.min(Math.max(n + delta, 0), last);
+    if (e.key === "Home") k = 0;
+    if (e.key === "End") k = last;
+    if (k !== null && k !== n) {
+        e.preventDefault();
+        location.search = `?n=${k}`;
+    }
+});
+})();
+</script>
+{% endblock %}
+"#)]
+struct StepTemplate {
+    id: i32,
+    problem_id: i32,
+    n: usize,
+    prev: usize,
+    next: usize,
+    num_moves: usize,
+    valid_len: usize,
+    cost: i64,
+    dist: i64,
+    num_blocks: usize,
+    frame_svg: String,
+    error: Option<(usize, String)>,
+    moves: Vec<StepMove>,
+}
+
+#[test]
+fn test_stepper_seek() {
+    let moves = Move::parse_many("
+        cut [0] [200, 200]
+        color [0.0] [255, 0, 0, 255]
+        merge [0.0] [0.1]
+        color [1] [0, 0, 255, 255]
+        swap [0.2] [0.3]
+        color [0.0] [0, 255, 0, 255]
+    ");
+    let mut stepper = Stepper::new(1, moves.clone());
+    assert_eq!(stepper.valid_len(), 5);
+    assert_eq!(stepper.error.as_ref().unwrap().0, 5);
+    assert_eq!(stepper.touched[0].len(), 4);
+
+    for &n in &[0, 3, 1, 5, 2] {
+        stepper.seek(n);
+        let mut painter = PainterState::new(leaked_problem(1));
+        for m in &moves[..n] {
+            painter.apply_move(m);
+        }
+        assert_eq!(stepper.painter.cost, painter.cost);
+        assert_eq!(stepper.painter.render(), painter.render());
+    }
+}
@@ -0,0 +1,191 @@
+// Exports contest results in one go, for the final report or for archiving:
+//     timeline.json, timeline.csv   every successful submission per problem,
+//                                   with the solver and invocation that produced it
+//     isl/{problem_id}/best.isl     winning solution
+//     isl/{problem_id}/{submission_id}.isl  each submission that improved the score
+//     rendered/{problem_id}.png     winning solution rendered (unless --no-render)
+//     summary.txt                   winning scores in a human-readable table
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use fxhash::FxHashMap as HashMap;
+use crate::util::DateTime;
+use crate::basic::*;
+use crate::store::{datetime_format, SolutionRow, Store, SubmissionResult};
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TimelineEntry {
+    pub problem_id: i32,
+    pub submission_id: i32,
+    #[serde(with = "datetime_format")]
+    pub submitted: DateTime,
+    #[serde(with = "datetime_format")]
+    pub found: DateTime,
+    pub cost: i64,  // official
+    pub solution_id: i32,
+    pub moves_cost: i64,
+    pub image_distance: i64,
+    pub solver_name: String,
+    pub solver_args: serde_json::Value,
+    pub invocation_id: i32,
+    // Better than all previous submissions for the problem.
+    pub improvement: bool,
+}
+
+// Successful submissions by problem, in the order of submission.
+pub fn submission_timelines(store: &mut dyn Store) -> BTreeMap<i32, Vec<TimelineEntry>> {
+    let mut solutions: HashMap<i32, SolutionRow> = store.solutions(None).into_iter().map(|s| (s.id, s)).collect();
+    let mut subs = store.submissions();
+    subs.sort_by_key(|sub| (sub.timestamp, sub.submission_id));
+    let mut timelines: BTreeMap<i32, Vec<TimelineEntry>> = BTreeMap::new();
+    for sub in subs {
+        let SubmissionResult::Good { cost, .. } = sub.result else { continue };
+        // duplicates aren't listed, but could have been submitted
+        let sol = solutions.entry(sub.solution_id).or_insert_with(|| store.solution(sub.solution_id));
+        let timeline = timelines.entry(sub.problem_id).or_default();
+        let improvement = timeline.iter().all(|e| cost < e.cost);
+        timeline.push(TimelineEntry {
+            problem_id: sub.problem_id,
+            submission_id: sub.submission_id,
+            submitted: sub.timestamp,
+            found: sol.timestamp,
+            cost,
+            solution_id: sol.id,
+            moves_cost: sol.moves_cost,
+            image_distance: sol.image_distance,
+            solver_name: sol.solver_name.clone(),
+            solver_args: sol.solver_args.clone(),
+            invocation_id: sol.invocation_id,
+            improvement,
+        });
+    }
+    timelines
+}
+
+// Last improvement, that is the best submission, first among equal ones.
+pub fn winner(timeline: &[TimelineEntry]) -> &TimelineEntry {
+    timeline.iter().rev().find(|e| e.improvement).unwrap()
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+pub fn timeline_csv(timelines: &BTreeMap<i32, Vec<TimelineEntry>>) -> String {
+    let mut res = String::new();
+    writeln!(res, "problem_id,submission_id,submitted,found,cost,solution_id,moves_cost,image_distance,\
+                   solver_name,solver_args,invocation_id,improvement").unwrap();
+    for e in timelines.values().flatten() {
+        writeln!(res, "{},{},{},{},{},{},{},{},{},{},{},{}",
+            e.problem_id, e.submission_id, e.submitted.to_rfc3339(), e.found.to_rfc3339(),
+            e.cost, e.solution_id, e.moves_cost, e.image_distance,
+            csv_field(&e.solver_name), csv_field(&e.solver_args.to_string()),
+            e.invocation_id, e.improvement).unwrap();
+    }
+    res
+}
+
+pub fn export(store: &mut dyn Store, out: &Path, render: bool) {
+    let timelines = submission_timelines(store);
+    std::fs::create_dir_all(out).unwrap();
+    let all: Vec<&TimelineEntry> = timelines.values().flatten().collect();
+    std::fs::write(out.join("timeline.json"), serde_json::to_string_pretty(&all).unwrap()).unwrap();
+    std::fs::write(out.join("timeline.csv"), timeline_csv(&timelines)).unwrap();
+
+    let mut summary = String::new();
+    // server is the score the server gave, ours is moves cost + image distance as we computed them,
+    // they can disagree (see reconcile_scores entry point)
+    writeln!(summary, "    server     ours =  cost  +  dist                 found            submitted  solver").unwrap();
+    for (&problem_id, timeline) in &timelines {
+        let dir = out.join("isl").join(problem_id.to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        for e in timeline.iter().filter(|e| e.improvement) {
+            std::fs::write(dir.join(format!("{}.isl", e.submission_id)), store.solution_data(e.solution_id)).unwrap();
+        }
+        let w = winner(timeline);
+        let sol = store.solution_data(w.solution_id);
+        std::fs::write(dir.join("best.isl"), &sol).unwrap();
+
+        if render {
+            let moves = Move::parse_many(&sol);
+            let problem = Problem::load(problem_id);
+            let mut painter = PainterState::new(&problem);
+            for m in &moves {
+                painter.apply_move(m);
+            }
+            std::fs::create_dir_all(out.join("rendered")).unwrap();
+            painter.render().save(&out.join("rendered").join(format!("{}.png", problem_id)));
+        }
+
+        writeln!(summary, "{problem_id:>2}    {:>5}    {:>5} = {:>5} + {:>5}   {}  {}  {}",
+            w.cost, w.moves_cost + w.image_distance, w.moves_cost, w.image_distance,
+            w.found.format("%Y-%m-%d %H:%M:%S"),
+            w.submitted.format("%Y-%m-%d %H:%M:%S"),
+            w.solver_name,
+        ).unwrap();
+    }
+    let total: i64 = timelines.values().map(|t| winner(t).cost).sum();
+    let our_total: i64 = timelines.values().map(|t| winner(t).moves_cost + winner(t).image_distance).sum();
+    writeln!(summary, "total {} (server), {} (ours)", total, our_total).unwrap();
+    std::fs::write(out.join("summary.txt"), &summary).unwrap();
+    eprintln!("exported {} problems to {}", timelines.len(), out.display());
+}
+
+crate::entry_point!("export_submissions", export_submissions);
+fn export_submissions() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let out: Option<String> = pargs.opt_value_from_str("--out").unwrap();
+    let no_render = pargs.contains("--no-render");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    let out = match out {
+        Some(out) => std::path::PathBuf::from(out),
+        None => crate::util::project_path("outputs/export"),
+    };
+    let mut store = crate::store::open();
+    export(&mut *store, &out, !no_render);
+}
+
+#[test]
+fn test_export() {
+    use crate::local_store::{insert_test_solution, TestStore};
+    use crate::store::InsertedSolution;
+
+    let mut store = TestStore::new();
+    let mut tx = store.transaction();
+    let InsertedSolution::New(s1) = insert_test_solution(&mut *tx, 1, 50, 1, 0) else { panic!() };
+    let InsertedSolution::New(s2) = insert_test_solution(&mut *tx, 1, 40, 2, 0) else { panic!() };
+    let InsertedSolution::New(s3) = insert_test_solution(&mut *tx, 1, 60, 3, 0) else { panic!() };
+    for (submission_id, solution_id, cost) in [(10, s1, 150), (11, s2, 145), (12, s3, 160)] {
+        tx.insert_submission(1, solution_id, submission_id);
+        tx.record_submission_result(submission_id, &SubmissionResult::Good { cost, file_url: String::new() });
+    }
+    tx.insert_submission(1, s3, 13);  // pending
+    tx.commit();
+
+    let timelines = submission_timelines(&mut *store);
+    let timeline = &timelines[&1];
+    assert_eq!(timeline.iter().map(|e| (e.submission_id, e.improvement)).collect::<Vec<_>>(),
+        vec![(10, true), (11, true), (12, false)]);
+    assert_eq!(winner(timeline).solution_id, s2);
+    let csv = timeline_csv(&timelines);
+    assert_eq!(csv.lines().count(), 4);
+    assert_eq!(csv_field("{\"a\":1,\"b\":2}"), "\"{\"\"a\"\":1,\"\"b\"\":2}\"");
+
+    let out = store.dir.join("out");
+    export(&mut *store, &out, false);
+    assert_eq!(std::fs::read_to_string(out.join("isl/1/best.isl")).unwrap(), store.solution_data(s2));
+    assert!(out.join("isl/1/10.isl").exists());
+    assert!(!out.join("isl/1/12.isl").exists());
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(out.join("timeline.json")).unwrap()).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 3);
+    // the server's cost and our score disagree here
+    let summary = std::fs::read_to_string(out.join("summary.txt")).unwrap();
+    assert!(summary.lines().nth(1).unwrap().starts_with(" 1      145      140 =    40 +   100 "), "{}", summary);
+}
@@ -0,0 +1,168 @@
+// Downloads problems into data/problems/.
+// Problems are listed through the API, their files are fetched from the CDN:
+//     {id}.png             target, always there
+//     {id}.initial.json    initial canvas, for problems with one
+//     {id}.initial.png     initial image, for problems with one
+//     {id}.source.png      image the initial one is made from, not used by us
+// Files are only written if they are new or changed,
+// and only after the problem is validated by loading it.
+
+use std::path::Path;
+use serde::Deserialize;
+
+use crate::api::{parse_response, ApiError, Conf};
+use crate::basic::Problem;
+
+#[derive(Deserialize, Debug)]
+struct ProblemList {
+    problems: Vec<ProblemInfo>,
+}
+
+// Fields we don't use are ignored.
+#[derive(Deserialize, Debug)]
+struct ProblemInfo {
+    id: i32,
+}
+
+pub fn list_problems(conf: &Conf) -> Result<Vec<i32>, ApiError> {
+    let r = conf.with_auth(ureq::get(&conf.url(&["problems"])));
+    let list: ProblemList = parse_response(r.call())?;
+    Ok(list.problems.into_iter().map(|p| p.id).collect())
+}
+
+fn problem_file_names(problem_id: i32) -> [String; 4] {
+    [
+        format!("{}.png", problem_id),
+        format!("{}.initial.json", problem_id),
+        format!("{}.initial.png", problem_id),
+        format!("{}.source.png", problem_id),
+    ]
+}
+
+// None if there is no such file.
+fn download(url: &str) -> Result<Option<Vec<u8>>, ApiError> {
+    let resp = match ureq::get(url).call() {
+        Ok(resp) => resp,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut data = vec![];
+    std::io::Read::read_to_end(&mut resp.into_reader(), &mut data)
+        .map_err(|e| ApiError::Transport(e.to_string()))?;
+    Ok(Some(data))
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FetchStats {
+    pub written: usize,
+    pub unchanged: usize,
+    pub invalid_problems: Vec<i32>,
+}
+
+// Downloads into staging_dir first, because that's where Problem::load_from() can look at it.
+pub fn fetch_problem(conf: &Conf, problem_id: i32, out_dir: &Path, staging_dir: &Path) -> Result<FetchStats, ApiError> {
+    if staging_dir.exists() {
+        std::fs::remove_dir_all(staging_dir).unwrap();
+    }
+    std::fs::create_dir_all(staging_dir).unwrap();
+    let mut files = vec![];
+    for name in problem_file_names(problem_id) {
+        if let Some(data) = download(&format!("{}/{}", conf.cdn_url, name))? {
+            std::fs::write(staging_dir.join(&name), &data).unwrap();
+            files.push((name, data));
+        }
+    }
+
+    let mut stats = FetchStats::default();
+    if let Err(e) = crate::invocation::catch_panic(|| Problem::load_from(staging_dir, problem_id)) {
+        eprintln!("problem {} is invalid, not writing it: {}", problem_id, e);
+        stats.invalid_problems.push(problem_id);
+        return Ok(stats);
+    }
+    std::fs::create_dir_all(out_dir).unwrap();
+    for (name, data) in files {
+        let path = out_dir.join(&name);
+        if std::fs::read(&path).is_ok_and(|old| old == data) {
+            stats.unchanged += 1;
+            continue;
+        }
+        eprintln!("{} {}", if path.exists() { "updating" } else { "adding" }, path.display());
+        std::fs::write(&path, &data).unwrap();
+        stats.written += 1;
+    }
+    Ok(stats)
+}
+
+pub fn fetch_problems(conf: &Conf, problem_ids: &[i32], out_dir: &Path, staging_dir: &Path) -> Result<FetchStats, ApiError> {
+    let mut stats = FetchStats::default();
+    for &problem_id in problem_ids {
+        let s = fetch_problem(conf, problem_id, out_dir, staging_dir)?;
+        stats.written += s.written;
+        stats.unchanged += s.unchanged;
+        stats.invalid_problems.extend(s.invalid_problems);
+    }
+    Ok(stats)
+}
+
+crate::entry_point!("fetch_problems", fetch_problems_ep);
+fn fetch_problems_ep() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: Option<String> = pargs.opt_value_from_str("--problem").unwrap();
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    let conf = Conf::load();
+    let mut problem_ids = list_problems(&conf).unwrap();
+    if let Some(problems) = problems {
+        let range = crate::util::parse_range(&problems);
+        problem_ids.retain(|id| range.contains(id));
+    }
+    eprintln!("fetching {} problems", problem_ids.len());
+    let stats = fetch_problems(
+        &conf, &problem_ids,
+        &crate::util::project_path("data/problems"),
+        &crate::util::project_path("cache/fetch_problems"),
+    ).unwrap();
+    eprintln!("{:?}", stats);
+}
+
+#[test]
+fn test_fetch_problems() {
+    let dir = crate::util::project_path("cache/test_fetch_problems");
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    let out_dir = dir.join("problems");
+    let staging_dir = dir.join("staging");
+    let problems_dir = crate::util::project_path("data/problems");
+
+    let mock = crate::mock_server::MockServer::start();
+    let mut num_files = 0;
+    for name in problem_file_names(1).into_iter().chain(problem_file_names(36)) {
+        if let Ok(data) = std::fs::read(problems_dir.join(&name)) {
+            mock.add_problem_file(&name, data);
+            num_files += 1;
+        }
+    }
+    assert!(num_files >= 4, "{}", num_files);  // 1.png, and at least 36.png and 36.initial.*
+    mock.add_problem_file("2.png", b"not a png".to_vec());
+    assert_eq!(list_problems(&mock.conf).unwrap(), vec![1, 2, 36]);
+
+    let stats = fetch_problems(&mock.conf, &[1, 2, 36], &out_dir, &staging_dir).unwrap();
+    assert_eq!(stats.invalid_problems, vec![2]);
+    assert_eq!((stats.written, stats.unchanged), (num_files, 0));
+    assert!(!out_dir.join("2.png").exists());
+    let p = Problem::load_from(&out_dir, 36);
+    assert_eq!(p.target.width, Problem::load(36).target.width);
+
+    // Nothing changed on the server, nothing is rewritten.
+    let stats = fetch_problems(&mock.conf, &[1, 36], &out_dir, &staging_dir).unwrap();
+    assert_eq!(stats, FetchStats { written: 0, unchanged: num_files, invalid_problems: vec![] });
+
+    mock.add_problem_file("1.png", std::fs::read(problems_dir.join("3.png")).unwrap());
+    let stats2 = fetch_problems(&mock.conf, &[1, 36], &out_dir, &staging_dir).unwrap();
+    assert_eq!(stats2, FetchStats { written: 1, unchanged: num_files - 1, invalid_problems: vec![] });
+    assert_eq!(std::fs::read(out_dir.join("1.png")).unwrap(), std::fs::read(problems_dir.join("3.png")).unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -25,6 +25,12 @@ impl Image {
         self.to_raw_image().save(path).unwrap();
     }
 
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        self.to_raw_image().write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
     pub fn to_raw_image(&self) -> image::RgbaImage {
         let mut img = image::RgbaImage::new(self.width as u32, self.height as u32);
         for (x, y, pixel) in img.enumerate_pixels_mut() {
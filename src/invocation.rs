@@ -1,27 +1,24 @@
 use std::{path::{Path, Component}};
-use postgres::{Client, Transaction, types::Json};
+use std::sync::{Mutex, PoisonError};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
 use once_cell::sync::{Lazy, OnceCell};
 
 use crate::util::{DateTime};
+use crate::store::Store;
 
 crate::entry_point!("invocation_demo", invocation_demo);
 fn invocation_demo() {
-    let mut client = crate::db::create_client();
+    let fail = std::env::args().any(|a| a == "--fail");
+    let invocation = InvocationGuard::start();
+    eprintln!("running as invocation {}...", invocation.id());
 
-    let mut tx = client.transaction().unwrap();
-    let id = record_this_invocation(&mut tx, Status::KeepRunning { seconds: 35.0 });
-    tx.commit().unwrap();
-    eprintln!("running as invocation {}...", id);
-
-    std::thread::sleep(std::time::Duration::from_secs(30));
-
-    let mut tx = client.transaction().unwrap();
-    record_this_invocation(&mut tx, Status::Stopped);
-    tx.commit().unwrap();
-    eprintln!("stopped");
+    std::thread::sleep(std::time::Duration::from_secs(70));
+    assert!(!fail, "failing on purpose");
+    eprintln!("stopping");
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Invocation {
     pub argv: Vec<String>,
@@ -30,9 +27,11 @@ pub struct Invocation {
     pub machine: String,
     pub distro: String,
     pub version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub panic_message: Option<String>,  // for FAILED invocations
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Version {
     pub commit: String,
@@ -47,6 +46,7 @@ pub static THIS_INVOCATION_ID: OnceCell<i32> = OnceCell::new();
 pub enum Status {
     KeepRunning { seconds: f64 },  // time to next planned call to record_this_invocation()
     Stopped,
+    Failed { panic_message: String },
 }
 
 #[derive(Debug)]
@@ -61,94 +61,167 @@ pub enum RecordedStatus {
     Running { start: DateTime, upcoming: DateTime },
     Stopped { start: DateTime, finish: DateTime },
     Lost { start: DateTime, lost: DateTime },
+    Failed { start: DateTime, finish: DateTime },
 }
 
 /// Creates or updates current invocation.
 /// Returns invocation ID, it will be the same if called mutliple times.
-pub fn record_this_invocation(t: &mut Transaction, status: Status) -> i32 {
+pub fn record_this_invocation(status: Status) -> i32 {
+    record_this_invocation_in(&mut *crate::store::open(), status)
+}
+
+/// Same, with the given store.
+/// It's always done in a separate transaction that is committed right away,
+/// so the cached ID stays valid even if the caller's own transaction
+/// is rolled back (for example, in --dry-run).
+pub fn record_this_invocation_in(store: &mut dyn Store, status: Status) -> i32 {
+    let mut data = std::borrow::Cow::Borrowed(&*THIS_INVOCATION);
     let (delta_time, status) = match status {
         Status::KeepRunning { seconds } => (seconds, "RUN"),
         Status::Stopped => (0.0, "STOPPED"),
+        Status::Failed { panic_message } => {
+            data.to_mut().panic_message = Some(panic_message);
+            (0.0, "FAILED")
+        }
     };
     let mut inserted = false;
     let id = *THIS_INVOCATION_ID.get_or_init(|| {
         inserted = true;
-        let row = t.query_one("
-            INSERT INTO invocations_raw(start_time, status, update_time, data)
-            VALUES(NOW(), $1, NOW() + $2 * interval '1 second', $3) RETURNING id
-            ", &[&status, &delta_time, &Json(&*THIS_INVOCATION)]).unwrap();
-        let id: i32 = row.get(0);
+        let mut tx = store.transaction();
+        let id = tx.record_invocation(None, status, delta_time, &data);
+        tx.commit();
         id
     });
     if !inserted {
-        t.execute("
-            UPDATE invocations_raw SET
-                status = $1,
-                update_time = NOW() + $2 * interval '1 second',
-                data = $3
-            WHERE id = $4
-        ", &[&status, &delta_time, &Json(&*THIS_INVOCATION), &id]).unwrap();
+        let mut tx = store.transaction();
+        tx.record_invocation(Some(id), status, delta_time, &data);
+        tx.commit();
     }
     id
 }
 
-pub fn get_invocations(client: &mut Client) -> Vec<Snapshot> {
-    let v = client.query("SELECT id, status, start_time, update_time, data FROM invocations", &[]).unwrap();
-    return v.iter().map(|row| {
-        let sql_id: i32 = row.get(0);
-        let raw_status: &str = row.get(1);
-        println!("{}", raw_status);
-        let t_0: DateTime = row.get(2);
-        let t_u: DateTime = row.get(3);
-        let invocation: Json<Invocation> = row.get(4);
-        let status = match raw_status {
-            "RUN" => RecordedStatus::Running { start: t_0, upcoming: t_u },
-            "STOPPED" => RecordedStatus::Stopped { start: t_0, finish: t_u },
-            _ => RecordedStatus::Lost { start: t_0, lost: t_u }
+const HEARTBEAT_PERIOD: Duration = Duration::from_secs(30);
+
+// Keeps the invocation in RUN status while it's alive,
+// and records STOPPED or FAILED (if dropped during a panic) at the end.
+//
+//     let invocation = InvocationGuard::start();
+//     ...
+//     upload_solution(&mut *tx, ..., invocation.id());
+pub struct InvocationGuard {
+    id: i32,
+    stop: Option<std::sync::mpsc::Sender<()>>,
+    heartbeat: Option<std::thread::JoinHandle<()>>,
+}
+
+impl InvocationGuard {
+    pub fn start() -> InvocationGuard {
+        install_panic_hook();
+        let keep_running = Status::KeepRunning { seconds: 2.0 * HEARTBEAT_PERIOD.as_secs_f64() };
+        let id = record_this_invocation(keep_running);
+
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let heartbeat = std::thread::Builder::new().name("heartbeat".to_owned()).spawn(move || {
+            // Its own connection, so it doesn't wait for whatever the main thread is doing with the DB.
+            let mut store = crate::store::open();
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_PERIOD) {
+                record_this_invocation_in(&mut *store, Status::KeepRunning { seconds: 2.0 * HEARTBEAT_PERIOD.as_secs_f64() });
+            }
+        }).unwrap();
+
+        InvocationGuard { id, stop: Some(stop), heartbeat: Some(heartbeat) }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Drop for InvocationGuard {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if self.heartbeat.take().unwrap().join().is_err() {
+            eprintln!("invocation heartbeat thread panicked");
+        }
+        let status = if std::thread::panicking() {
+            let panic_message = PANIC_MESSAGE.lock().unwrap_or_else(PoisonError::into_inner).take()
+                .unwrap_or_else(|| "<unknown>".to_owned());
+            Status::Failed { panic_message }
+        } else {
+            Status::Stopped
         };
-        Snapshot{
-            sql_id,
-            status,
-            invocation: invocation.0,
+        // The panic we are unwinding from may have come from the DB itself.
+        // Panicking again here would abort the process.
+        if let Err(e) = catch_panic(|| record_this_invocation(status)) {
+            eprintln!("can't record the end of invocation {}: {}", self.id, e);
         }
-    }).collect();
+    }
 }
 
-pub fn create_table(client: &mut Client) {
-    client.batch_execute("
-    CREATE TABLE IF NOT EXISTS invocations_raw(
-        id SERIAL PRIMARY KEY,
+// First panic in any thread. When a worker thread panics, the main thread
+// usually panics too (on join), but the original message is more useful.
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
 
-        status TEXT NOT NULL,
-        -- 'RUN'
-        -- 'STOPPED'
+thread_local! {
+    // Set inside catch_panic(), such panics are expected and not reported.
+    static CAUGHT_PANIC: std::cell::RefCell<Option<Option<String>>> = const { std::cell::RefCell::new(None) };
+}
 
-        start_time TIMESTAMP WITH TIME ZONE NOT NULL,
+fn install_panic_hook() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let caught = CAUGHT_PANIC.with(|c| match &mut *c.borrow_mut() {
+                Some(message) => {
+                    *message = Some(info.to_string());
+                    true
+                }
+                None => false,
+            });
+            if caught {
+                return;
+            }
+            PANIC_MESSAGE.lock().unwrap().get_or_insert_with(|| info.to_string());
+            default_hook(info);
+        }));
+    });
+}
 
-        update_time TIMESTAMP WITH TIME ZONE NOT NULL,
-        -- If status is 'STOPPED', termination time.
-        -- If status is 'RUN', time of the next planned update.
-        -- So if status == 'RUN' and update_time < NOW(),
-        -- we can conclude that the invocation terminated abnormally
-        -- without updating the DB.
-        -- Let's call it 'LOST' (even though such status will never appear
-        -- in the DB explicitly).
+// For code that signals errors by panicking (like PainterState::apply_move()).
+// Returns the panic message instead of printing it.
+pub fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    install_panic_hook();
+    CAUGHT_PANIC.with(|c| *c.borrow_mut() = Some(None));
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    let message = CAUGHT_PANIC.with(|c| c.borrow_mut().take().unwrap());
+    res.map_err(|_| message.unwrap_or_else(|| "<unknown>".to_owned()))
+}
 
-        data JSONB NOT NULL
-    );
+#[test]
+fn test_catch_panic() {
+    assert_eq!(catch_panic(|| 42), Ok(42));
+    let err = catch_panic(|| -> i32 { panic!("oops {}", 1) }).unwrap_err();
+    assert!(err.contains("oops 1"), "{}", err);
+    assert_eq!(catch_panic(|| 43), Ok(43));
+}
 
-    CREATE OR REPLACE VIEW invocations AS
-    SELECT
-        id,
-        CASE
-            WHEN status = 'RUN' AND update_time < NOW() THEN 'LOST'
-            ELSE status
-        END AS status,
-        start_time,
-        update_time,
-        data
-    FROM invocations_raw;
-    ").unwrap();
+pub fn get_invocations(store: &mut dyn Store) -> Vec<Snapshot> {
+    return store.invocations().into_iter().map(|row| {
+        let t_0 = row.start_time;
+        let t_u = row.update_time;
+        let status = match row.status.as_str() {
+            "RUN" => RecordedStatus::Running { start: t_0, upcoming: t_u },
+            "STOPPED" => RecordedStatus::Stopped { start: t_0, finish: t_u },
+            "FAILED" => RecordedStatus::Failed { start: t_0, finish: t_u },
+            _ => RecordedStatus::Lost { start: t_0, lost: t_u }
+        };
+        Snapshot{
+            sql_id: row.id,
+            status,
+            invocation: row.data,
+        }
+    }).collect();
 }
 
 impl Invocation {
@@ -181,6 +254,7 @@ impl Invocation {
             machine: whoami::hostname(),
             distro: whoami::distro(),
             version: Version::new(),
+            panic_message: None,
         }
     }
 }
@@ -0,0 +1,499 @@
+// Store backend that keeps everything in JSON lines files,
+// for working offline (TBD_STORE=local, see store.rs).
+//
+// Files are append-only, updated records (invocations, score estimates)
+// are appended again and the last one wins.
+// A transaction holds an exclusive lock on the "lock" file from start to commit,
+// so several processes (and threads) can share a directory without their IDs colliding.
+// Readers don't lock, commits append whole lines with one write per file.
+
+use std::io::Write;
+use std::path::PathBuf;
+use fxhash::FxHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::basic::Move;
+use crate::invocation::Invocation;
+use crate::score_estimate::ScoreEstimate;
+use crate::store::*;
+use crate::util::DateTime;
+
+const SOLUTIONS: &str = "solutions.jsonl";
+const INVOCATIONS: &str = "invocations.jsonl";
+const SUBMISSIONS: &str = "submissions.jsonl";
+const SUBMISSION_RESULTS: &str = "submission_results.jsonl";
+const SCORE_ESTIMATES: &str = "score_estimates.jsonl";
+const VERIFICATIONS: &str = "verifications.jsonl";
+const SCORE_MISMATCHES: &str = "score_mismatches.jsonl";
+const TRIAGES: &str = "triages.jsonl";
+const LOCK: &str = "lock";
+
+#[derive(Serialize, Deserialize)]
+struct SolutionRecord {
+    #[serde(flatten)]
+    row: SolutionRow,
+    data: String,
+    #[serde(default)]
+    content_hash: Option<String>,
+}
+
+impl SolutionRecord {
+    fn content_hash(&self) -> String {
+        self.content_hash.clone().unwrap_or_else(|| content_hash(&Move::parse_many(&self.data)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct InvocationRecord {
+    id: i32,
+    status: String,
+    #[serde(with = "datetime_format")]
+    start_time: DateTime,
+    #[serde(with = "datetime_format")]
+    update_time: DateTime,
+    data: Invocation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubmissionRecord {
+    submission_id: i32,
+    problem_id: i32,
+    solution_id: i32,
+    #[serde(with = "datetime_format")]
+    timestamp: DateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubmissionResultRecord {
+    submission_id: i32,
+    result: SubmissionResult,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScoreEstimateRecord {
+    problem_id: i32,
+    #[serde(flatten)]
+    est: ScoreEstimate,
+}
+
+pub struct LocalStore {
+    dir: PathBuf,
+    exclude_invalid: bool,
+}
+
+impl LocalStore {
+    pub fn new(dir: PathBuf) -> LocalStore {
+        std::fs::create_dir_all(&dir).unwrap();
+        LocalStore { dir, exclude_invalid: false }
+    }
+
+    fn read<T: DeserializeOwned>(&self, name: &str) -> Vec<T> {
+        let path = self.dir.join(name);
+        if !path.exists() {
+            return vec![];
+        }
+        std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)))
+            .collect()
+    }
+
+    fn read_solutions(&self) -> Vec<SolutionRecord> {
+        self.read(SOLUTIONS)
+    }
+
+    fn read_invocations(&self) -> Vec<InvocationRow> {
+        let now = chrono::Utc::now();
+        let mut latest: HashMap<i32, InvocationRecord> = HashMap::default();
+        for r in self.read::<InvocationRecord>(INVOCATIONS) {
+            latest.insert(r.id, r);
+        }
+        let mut rows: Vec<InvocationRow> = latest.into_values().map(|r| InvocationRow {
+            id: r.id,
+            status: if r.status == "RUN" && r.update_time < now { "LOST".to_owned() } else { r.status },
+            start_time: r.start_time,
+            update_time: r.update_time,
+            data: r.data,
+        }).collect();
+        rows.sort_by_key(|r| r.id);
+        rows
+    }
+}
+
+impl Store for LocalStore {
+    fn transaction(&mut self) -> Box<dyn StoreTransaction + '_> {
+        let lock = std::fs::OpenOptions::new().create(true).truncate(false).write(true)
+            .open(self.dir.join(LOCK)).unwrap();
+        lock.lock().unwrap();
+        Box::new(LocalTransaction { store: self, pending: vec![], _lock: lock })
+    }
+
+    fn find_solution(&mut self, id: i32) -> Option<SolutionRow> {
+        self.read_solutions().into_iter().find(|s| s.row.id == id).map(|s| s.row)
+    }
+
+    fn solution_data(&mut self, id: i32) -> String {
+        self.read_solutions().into_iter().find(|s| s.row.id == id)
+            .unwrap_or_else(|| panic!("no solution {}", id)).data
+    }
+
+    fn solutions(&mut self, problem_id: Option<i32>) -> Vec<SolutionRow> {
+        let mut rows: Vec<SolutionRow> = self.read_solutions().into_iter()
+            .map(|s| s.row)
+            .filter(|s| problem_id.is_none_or(|p| s.problem_id == p))
+            .collect();
+        rows.sort_by_key(|s| s.timestamp);
+        rows
+    }
+
+    fn best_solution(&mut self, problem_id: i32) -> Option<SolutionRow> {
+        self.leaderboard_candidates(Some(problem_id)).into_iter().min_by_key(|s| (s.score, s.timestamp))
+    }
+
+    fn exclude_invalid(&self) -> bool {
+        self.exclude_invalid
+    }
+
+    fn set_exclude_invalid(&mut self, exclude: bool) {
+        self.exclude_invalid = exclude;
+    }
+
+    fn find_invocation(&mut self, id: i32) -> Option<InvocationRow> {
+        self.read_invocations().into_iter().find(|r| r.id == id)
+    }
+
+    fn invocations(&mut self) -> Vec<InvocationRow> {
+        self.read_invocations()
+    }
+
+    fn submissions(&mut self) -> Vec<SubmissionRow> {
+        let mut results: HashMap<i32, SubmissionResult> = HashMap::default();
+        for r in self.read::<SubmissionResultRecord>(SUBMISSION_RESULTS) {
+            results.entry(r.submission_id).or_insert(r.result);
+        }
+        let mut rows: Vec<SubmissionRow> = self.read::<SubmissionRecord>(SUBMISSIONS).into_iter().map(|r| SubmissionRow {
+            submission_id: r.submission_id,
+            problem_id: r.problem_id,
+            solution_id: r.solution_id,
+            timestamp: r.timestamp,
+            result: results.remove(&r.submission_id).unwrap_or(SubmissionResult::Pending),
+        }).collect();
+        rows.sort_by_key(|r| r.timestamp);
+        rows
+    }
+
+    fn score_estimates(&mut self) -> Vec<(i32, ScoreEstimate)> {
+        let mut latest: HashMap<i32, ScoreEstimate> = HashMap::default();
+        for r in self.read::<ScoreEstimateRecord>(SCORE_ESTIMATES) {
+            latest.insert(r.problem_id, r.est);
+        }
+        latest.into_iter().collect()
+    }
+
+    fn verifications(&mut self) -> Vec<Verification> {
+        let mut latest: HashMap<i32, Verification> = HashMap::default();
+        for v in self.read::<Verification>(VERIFICATIONS) {
+            latest.insert(v.solution_id, v);
+        }
+        latest.into_values().collect()
+    }
+
+    fn score_mismatches(&mut self) -> Vec<ScoreMismatch> {
+        let mut latest: HashMap<i32, ScoreMismatch> = HashMap::default();
+        for m in self.read::<ScoreMismatch>(SCORE_MISMATCHES) {
+            latest.insert(m.submission_id, m);
+        }
+        let mut res: Vec<ScoreMismatch> = latest.into_values().collect();
+        res.sort_by_key(|m| m.submission_id);
+        res
+    }
+
+    fn triages(&mut self) -> Vec<Triage> {
+        let mut latest: HashMap<i32, Triage> = HashMap::default();
+        for t in self.read::<Triage>(TRIAGES) {
+            latest.insert(t.submission_id, t);
+        }
+        let mut res: Vec<Triage> = latest.into_values().collect();
+        res.sort_by_key(|t| t.submission_id);
+        res
+    }
+}
+
+struct LocalTransaction<'a> {
+    store: &'a mut LocalStore,
+    pending: Vec<(&'static str, String)>,  // (file name, json line)
+    // released when the transaction is dropped, committed or not
+    _lock: std::fs::File,
+}
+
+impl LocalTransaction<'_> {
+    fn push(&mut self, name: &'static str, record: &impl Serialize) {
+        self.pending.push((name, serde_json::to_string(record).unwrap()));
+    }
+
+    fn pending<T: DeserializeOwned>(&self, name: &str) -> Vec<T> {
+        self.pending.iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, line)| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl StoreTransaction for LocalTransaction<'_> {
+    fn record_invocation(&mut self, id: Option<i32>, status: &str, delta_time: f64, data: &Invocation) -> i32 {
+        let now = chrono::Utc::now();
+        let update_time = now + chrono::Duration::milliseconds((delta_time * 1000.0) as i64);
+        let mut records: Vec<InvocationRecord> = self.store.read(INVOCATIONS);
+        records.extend(self.pending::<InvocationRecord>(INVOCATIONS));
+        let (id, start_time) = match id {
+            None => (records.iter().map(|r| r.id).max().unwrap_or(0) + 1, now),
+            Some(id) => {
+                let start_time = records.iter().find(|r| r.id == id)
+                    .unwrap_or_else(|| panic!("no invocation {}", id)).start_time;
+                (id, start_time)
+            }
+        };
+        self.push(INVOCATIONS, &InvocationRecord { id, status: status.to_owned(), start_time, update_time, data: data.clone() });
+        id
+    }
+
+    fn insert_solution(&mut self, s: &NewSolution) -> InsertedSolution {
+        let mut records = self.store.read_solutions();
+        records.extend(self.pending::<SolutionRecord>(SOLUTIONS));
+        if let Some(r) = records.iter().find(|r| r.row.problem_id == s.problem_id && r.content_hash() == s.content_hash) {
+            return InsertedSolution::AlreadyKnown(r.row.id);
+        }
+        let id = records.iter().map(|r| r.row.id).max().unwrap_or(0) + 1;
+        let row = SolutionRow {
+            id,
+            problem_id: s.problem_id,
+            moves_cost: s.moves_cost,
+            image_distance: s.image_distance,
+            score: s.moves_cost + s.image_distance,
+            solver_name: s.solver_name.to_owned(),
+            solver_args: s.solver_args.clone(),
+            solver_args_version: s.solver_args_version,
+            invocation_id: s.invocation_id,
+            timestamp: chrono::Utc::now(),
+            parent_id: s.derivation.map(|d| d.parent_id),
+            derivation: s.derivation.map(|d| d.label.to_owned()),
+        };
+        self.push(SOLUTIONS, &SolutionRecord { row, data: s.data.to_owned(), content_hash: Some(s.content_hash.to_owned()) });
+        InsertedSolution::New(id)
+    }
+
+    fn insert_submission(&mut self, problem_id: i32, solution_id: i32, submission_id: i32) {
+        self.push(SUBMISSIONS, &SubmissionRecord { submission_id, problem_id, solution_id, timestamp: chrono::Utc::now() });
+    }
+
+    fn record_submission_result(&mut self, submission_id: i32, result: &SubmissionResult) {
+        if let SubmissionResult::Pending = result {
+            return;
+        }
+        // Only the first recorded result counts, see LocalStore::submissions().
+        self.push(SUBMISSION_RESULTS, &SubmissionResultRecord { submission_id, result: result.clone() });
+    }
+
+    fn set_score_estimate(&mut self, problem_id: i32, est: &ScoreEstimate) {
+        self.push(SCORE_ESTIMATES, &ScoreEstimateRecord { problem_id, est: est.clone() });
+    }
+
+    fn record_verification(&mut self, v: &Verification) {
+        self.push(VERIFICATIONS, v);
+    }
+
+    fn record_score_mismatch(&mut self, m: &ScoreMismatch) {
+        self.push(SCORE_MISMATCHES, m);
+    }
+
+    fn record_triage(&mut self, t: &Triage) {
+        self.push(TRIAGES, t);
+    }
+
+    fn commit(self: Box<Self>) {
+        let mut files: HashMap<&str, String> = HashMap::default();
+        for (name, line) in &self.pending {
+            let s = files.entry(name).or_default();
+            s.push_str(line);
+            s.push('\n');
+        }
+        for (name, s) in files {
+            std::fs::OpenOptions::new().create(true).append(true)
+                .open(self.store.dir.join(name)).unwrap()
+                .write_all(s.as_bytes()).unwrap();
+        }
+    }
+}
+
+// LocalStore in a fresh temporary directory, which is removed when this is dropped,
+// even if the test panics.
+#[cfg(test)]
+pub(crate) struct TestStore {
+    // the store itself is in dir/store, the rest is for whatever else the test writes
+    pub dir: PathBuf,
+    store: LocalStore,
+}
+
+#[cfg(test)]
+impl TestStore {
+    pub fn new() -> TestStore {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("icfpc2022-tbd-test-{}-{}", std::process::id(), n));
+        let store = LocalStore::new(dir.join("store"));
+        TestStore { dir, store }
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestStore {
+    type Target = LocalStore;
+    fn deref(&self) -> &LocalStore {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+impl std::ops::DerefMut for TestStore {
+    fn deref_mut(&mut self) -> &mut LocalStore {
+        &mut self.store
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestStore {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            eprintln!("can't remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn insert_test_solution(tx: &mut dyn StoreTransaction, problem_id: i32, moves_cost: i64, color: u8, invocation_id: i32) -> InsertedSolution {
+    let moves = Move::parse_many(&format!("color [0] [{}, 2, 3, 4]", color));
+    tx.insert_solution(&NewSolution {
+        problem_id,
+        data: &canonical_text(&moves),
+        content_hash: &content_hash(&moves),
+        moves_cost,
+        image_distance: 100,
+        solver_name: "test",
+        solver_args: &serde_json::Value::Null,
+        solver_args_version: None,
+        invocation_id,
+        derivation: None,
+    })
+}
+
+#[test]
+fn test_local_store() {
+    let mut store = TestStore::new();
+    let inv = Invocation {
+        argv: vec!["test".to_owned()],
+        profile: "debug".to_owned(),
+        user: "user".to_owned(),
+        machine: "machine".to_owned(),
+        distro: "distro".to_owned(),
+        version: crate::invocation::Version {
+            commit: "0".repeat(40),
+            commit_number: 1,
+            diff_stat: String::new(),
+        },
+        panic_message: None,
+    };
+
+    let mut tx = store.transaction();
+    let inv_id = tx.record_invocation(None, "RUN", 10.0, &inv);
+    let InsertedSolution::New(s1) = insert_test_solution(&mut *tx, 1, 50, 1, inv_id) else { panic!() };
+    let InsertedSolution::New(s2) = insert_test_solution(&mut *tx, 1, 20, 2, inv_id) else { panic!() };
+    let InsertedSolution::New(s3) = insert_test_solution(&mut *tx, 1, 20, 3, inv_id) else { panic!() };
+    let InsertedSolution::New(s4) = insert_test_solution(&mut *tx, 2, 10, 1, inv_id) else { panic!() };
+    tx.commit();
+
+    // Not committed.
+    let mut tx = store.transaction();
+    insert_test_solution(&mut *tx, 1, 0, 4, inv_id);
+    drop(tx);
+
+    let mut tx = store.transaction();
+    assert!(matches!(insert_test_solution(&mut *tx, 1, 20, 2, inv_id), InsertedSolution::AlreadyKnown(id) if id == s2));
+    drop(tx);
+
+    let mut tx = store.transaction();
+    assert_eq!(tx.record_invocation(Some(inv_id), "STOPPED", 0.0, &inv), inv_id);
+    tx.insert_submission(1, s2, 777);
+    tx.commit();
+
+    assert_eq!(store.solutions(None).len(), 4);
+    assert_eq!(store.solutions(Some(1)).iter().map(|s| s.id).collect::<Vec<_>>(), vec![s1, s2, s3]);
+    assert_eq!(store.best_solution(1).unwrap().id, s2);
+    assert!(store.best_solution(3).is_none());
+    let ids = |rows: Vec<SolutionRow>| rows.iter().map(|s| s.id).collect::<Vec<_>>();
+    assert_eq!(ids(store.best_solutions()), vec![s2, s4]);
+    assert_eq!(ids(store.top_solutions(2)), vec![s2, s3, s4]);
+    assert_eq!(ids(store.solver_bests()), vec![s2, s4]);
+    let lb = leaderboard(&mut *store, Some(&[1, 3]));
+    assert_eq!(lb[0].1.as_ref().unwrap().id, s2);
+    assert!(lb[1].1.is_none());
+    assert_eq!(store.solution_data(s1), "color [0] [1, 2, 3, 4]\n");
+    assert_eq!(store.invocations().len(), 1);
+    assert_eq!(store.invocation(inv_id).status, "STOPPED");
+
+    assert!(matches!(store.submissions()[0].result, SubmissionResult::Pending));
+    let mut tx = store.transaction();
+    tx.record_submission_result(777, &SubmissionResult::Good { cost: 120, file_url: "x".to_owned() });
+    tx.record_submission_result(777, &SubmissionResult::Bad { error: "y".to_owned(), file_url: "x".to_owned() });
+    tx.commit();
+    assert!(matches!(store.submissions()[0].result, SubmissionResult::Good { cost: 120, .. }));
+
+    let verification = |solution_id, error: Option<&str>| Verification {
+        solution_id,
+        moves_cost: None,
+        image_distance: None,
+        error: error.map(str::to_owned),
+        invocation_id: inv_id,
+        timestamp: chrono::Utc::now(),
+    };
+    let mut tx = store.transaction();
+    tx.record_verification(&verification(s2, None));
+    tx.record_verification(&verification(s2, Some("invalid move")));
+    tx.record_verification(&verification(s4, None));
+    tx.commit();
+    assert_eq!(store.verifications().len(), 2);
+    assert_eq!(ids(store.best_solutions()), vec![s2, s4]);
+    store.set_exclude_invalid(true);
+    assert_eq!(store.best_solution(1).unwrap().id, s3);
+    assert_eq!(ids(store.best_solutions()), vec![s3, s4]);
+    assert_eq!(ids(store.solver_bests()), vec![s3, s4]);
+}
+
+#[test]
+fn test_concurrent_transactions() {
+    let test_store = TestStore::new();
+    let dir = &test_store.store.dir;
+    let ids: Vec<i32> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4u8).map(|t| {
+            let dir = dir.clone();
+            scope.spawn(move || {
+                let mut store = LocalStore::new(dir);
+                (0..5u8).map(|i| {
+                    let mut tx = store.transaction();
+                    let InsertedSolution::New(id) = insert_test_solution(&mut *tx, 1, 50, t * 10 + i, 0) else { panic!() };
+                    // so that the others get a chance to allocate the same id
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                    tx.commit();
+                    id
+                }).collect::<Vec<_>>()
+            })
+        }).collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+    let mut unique = ids.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 20);
+    assert_eq!(LocalStore::new(dir.clone()).solutions(None).len(), 20);
+}
@@ -7,18 +7,30 @@ pub mod basic;
 mod color_util;
 mod dashboard;
 mod db;
+mod export;
+mod fetch_problems;
 mod image;
 mod invocation;
 mod kyeet;
+mod local_store;
+mod score_estimate;
+mod mock_server;
+mod migrations;
 mod pack;
+mod reconcile;
+mod palette;
 mod print_invocations;
 mod seg_util;
+mod solver_args;
 mod solvers;
 mod stats;
+mod store;
+mod submitter;
 mod transform;
+mod triage;
 mod uploader;
+mod verify;
 mod util;
-mod dump_final_submissions;
 
 crate::entry_point!("hello", hello);
 fn hello() {
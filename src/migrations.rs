@@ -0,0 +1,104 @@
+// Schema changes for the Postgres DB.
+// To change the schema, add migrations/NNN_whatever.sql and a line to MIGRATIONS.
+// Never edit migrations that were already applied to the shared DB.
+//
+//   cargo run migrate           # apply pending migrations
+//   cargo run migrate --status  # only show what's applied and what's pending
+
+use crate::util::DateTime;
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../migrations/", $name, ".sql")),
+        }
+    };
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "001_initial"),
+    migration!(2, "002_content_hash"),
+    migration!(3, "003_lineage"),
+    migration!(4, "004_solver_args_version"),
+    migration!(5, "005_verifications"),
+    migration!(6, "006_score_mismatches"),
+    migration!(7, "007_submission_triage"),
+    migration!(8, "008_score_estimates"),
+    migration!(9, "009_triage_solution_invocation"),
+];
+
+fn create_version_table(client: &mut postgres::Client) {
+    client.batch_execute("
+    CREATE TABLE IF NOT EXISTS schema_version(
+        version INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at TIMESTAMP WITH TIME ZONE NOT NULL
+    );
+    ").unwrap();
+}
+
+fn applied_versions(client: &mut impl postgres::GenericClient) -> Vec<(i32, String, DateTime)> {
+    client.query("SELECT version, name, applied_at FROM schema_version ORDER BY version", &[]).unwrap()
+        .iter().map(|r| (r.get("version"), r.get("name"), r.get("applied_at"))).collect()
+}
+
+// Applies every pending migration in its own transaction.
+// The lock makes concurrent runs wait for each other instead of applying the same migration twice.
+pub fn migrate(client: &mut postgres::Client) {
+    create_version_table(client);
+    for m in MIGRATIONS {
+        let mut tx = client.transaction().unwrap();
+        tx.batch_execute("LOCK TABLE schema_version IN EXCLUSIVE MODE").unwrap();
+        if applied_versions(&mut tx).iter().any(|(v, _, _)| *v == m.version) {
+            continue;
+        }
+        eprintln!("applying {} ...", m.name);
+        tx.batch_execute(m.sql).unwrap_or_else(|e| panic!("migration {} failed: {}", m.name, e));
+        tx.execute(
+            "INSERT INTO schema_version(version, name, applied_at) VALUES ($1, $2, NOW())",
+            &[&m.version, &m.name]).unwrap();
+        tx.commit().unwrap();
+    }
+}
+
+crate::entry_point!("migrate", migrate_entry_point);
+fn migrate_entry_point() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let status_only = pargs.contains("--status");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    let mut client = crate::db::POOL.get();
+    if !status_only {
+        migrate(&mut client);
+    }
+    create_version_table(&mut client);
+    let applied = applied_versions(&mut *client);
+    for m in MIGRATIONS {
+        match applied.iter().find(|(v, _, _)| *v == m.version) {
+            Some((_, _, applied_at)) => println!("{:>4} {:<30} applied {}", m.version, m.name, applied_at),
+            None => println!("{:>4} {:<30} PENDING", m.version, m.name),
+        }
+    }
+    for (version, name, _) in &applied {
+        if !MIGRATIONS.iter().any(|m| m.version == *version) {
+            println!("{:>4} {:<30} applied, but unknown to this version of the code", version, name);
+        }
+    }
+}
+
+#[test]
+fn test_migrations_are_numbered() {
+    for (i, m) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(m.version, i as i32 + 1);
+        assert!(m.name.starts_with(&format!("{:03}_", m.version)), "{}", m.name);
+    }
+}
@@ -0,0 +1,240 @@
+// In-process stand-in for the contest server, so that API code can be exercised offline.
+// Implements just the endpoints we use, see api.rs and fetch_problems.rs:
+//     POST /api/problems/{problem_id}    multipart form with "file"
+//     GET  /api/submissions/{submission_id}
+//     GET  /api/problems
+//     GET  /cdn/{problem_id}.png, etc.
+//
+//     let mock = MockServer::start();
+//     let submission_id = crate::api::submit_solution(&mock.conf, store, solution_id)?;
+//
+// or standalone: cargo run mock_server, and TBD_API_URL=http://127.0.0.1:8001/api for everything else.
+
+use std::sync::Mutex;
+use fxhash::FxHashMap as HashMap;
+
+use crate::api::Conf;
+use crate::basic::*;
+use crate::dashboard::dev_server::{serve_forever, Request, ResponseBuilder, HandlerResult};
+
+pub struct MockServer {
+    pub conf: Conf,
+    state: &'static Mutex<MockState>,
+}
+
+// Scores submitted ISL for a problem, Err means the submission is rejected.
+pub type Scorer = fn(problem_id: i32, isl: &str) -> Result<i64, String>;
+
+struct MockState {
+    scorer: Scorer,
+    // Submissions are reported as processing for this many status requests.
+    pending_polls: usize,
+    // To simulate server trouble, this many next requests fail with 503.
+    failures: usize,
+    submissions: Vec<MockSubmission>,
+    // File name -> content, like in data/problems/.
+    problem_files: HashMap<String, Vec<u8>>,
+}
+
+struct MockSubmission {
+    problem_id: i32,
+    isl: String,
+    polls: usize,
+}
+
+const FIRST_SUBMISSION_ID: i32 = 1000;
+
+impl MockServer {
+    // On a random port.
+    #[cfg(test)]
+    pub fn start() -> MockServer {
+        MockServer::start_on("127.0.0.1:0")
+    }
+
+    pub fn start_on(addr: &str) -> MockServer {
+        let listener = std::net::TcpListener::bind(addr).unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Leaked because the server thread is never stopped.
+        let state: &'static Mutex<MockState> = Box::leak(Box::new(Mutex::new(MockState {
+            scorer: replay_score,
+            pending_polls: 0,
+            failures: 0,
+            submissions: vec![],
+            problem_files: HashMap::default(),
+        })));
+        std::thread::Builder::new().name("mock server".to_owned()).spawn(move || {
+            serve_forever(listener, move |req, resp| handler(state, req, resp))
+        }).unwrap();
+        MockServer {
+            conf: Conf {
+                url: format!("http://{}/api", addr),
+                token: Some("mock".to_owned()),
+                cdn_url: format!("http://{}/cdn", addr),
+            },
+            state,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_scorer(&self, scorer: Scorer) {
+        self.state.lock().unwrap().scorer = scorer;
+    }
+
+    pub fn set_pending_polls(&self, n: usize) {
+        self.state.lock().unwrap().pending_polls = n;
+    }
+
+    // Problems are listed if their target {problem_id}.png is added.
+    pub fn add_problem_file(&self, name: &str, data: Vec<u8>) {
+        self.state.lock().unwrap().problem_files.insert(name.to_owned(), data);
+    }
+
+    #[cfg(test)]
+    pub fn fail_next(&self, n: usize) {
+        self.state.lock().unwrap().failures = n;
+    }
+
+    // (submission_id, problem_id, isl) of everything submitted so far.
+    #[cfg(test)]
+    pub fn submissions(&self) -> Vec<(i32, i32, String)> {
+        self.state.lock().unwrap().submissions.iter().enumerate()
+            .map(|(i, s)| (FIRST_SUBMISSION_ID + i as i32, s.problem_id, s.isl.clone()))
+            .collect()
+    }
+}
+
+crate::entry_point!("mock_server", mock_server);
+fn mock_server() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let port: u16 = pargs.opt_value_from_str("--port").unwrap().unwrap_or(8001);
+    let pending_polls: usize = pargs.opt_value_from_str("--pending-polls").unwrap().unwrap_or(0);
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    let mock = MockServer::start_on(&format!("127.0.0.1:{}", port));
+    mock.set_pending_polls(pending_polls);
+    for e in std::fs::read_dir(crate::util::project_path("data/problems")).unwrap() {
+        let e = e.unwrap();
+        mock.add_problem_file(e.file_name().to_str().unwrap(), std::fs::read(e.path()).unwrap());
+    }
+    eprintln!("mock contest server, use it with TBD_API_URL={}", mock.conf.url);
+    loop {
+        std::thread::park();
+    }
+}
+
+// Same as the real server, modulo rounding.
+fn replay_score(problem_id: i32, isl: &str) -> Result<i64, String> {
+    crate::invocation::catch_panic(|| {
+        let problem = Problem::load(problem_id);
+        let mut painter = PainterState::new(&problem);
+        for m in Move::parse_many(isl) {
+            painter.apply_move(&m);
+        }
+        let img = painter.render();
+        painter.cost + image_distance(&img, &problem.target).round() as i64
+    })
+}
+
+fn handler(state: &Mutex<MockState>, req: Request, resp: ResponseBuilder) -> HandlerResult {
+    let json = |resp: ResponseBuilder, v: serde_json::Value| {
+        resp.code("200 OK").header("Content-Type", "application/json").body(v.to_string())
+    };
+
+    {
+        let mut state = state.lock().unwrap();
+        if state.failures > 0 {
+            state.failures -= 1;
+            return resp.code("503 Service Unavailable").body("try again later");
+        }
+    }
+
+    // Every endpoint but submitting is GET.
+    let expected_method = if req.path.starts_with("/api/problems/") { "POST" } else { "GET" };
+    if req.method != expected_method {
+        return resp.code("405 Method Not Allowed").header("Allow", expected_method)
+            .body(format!("expected {}", expected_method));
+    }
+
+    if req.path == "/api/problems" {
+        let state = state.lock().unwrap();
+        let mut ids: Vec<i32> = state.problem_files.keys()
+            .filter_map(|name| name.strip_suffix(".png")?.parse().ok())
+            .collect();
+        ids.sort();
+        let problems: Vec<serde_json::Value> = ids.iter()
+            .map(|id| serde_json::json!({ "id": id, "name": format!("problem {}", id) }))
+            .collect();
+        return json(resp, serde_json::json!({ "problems": problems }));
+    }
+
+    if let Some(name) = req.path.strip_prefix("/cdn/") {
+        return match state.lock().unwrap().problem_files.get(name) {
+            Some(data) => resp.code("200 OK").body(data),
+            None => resp.code("404 Not Found").body("not found"),
+        };
+    }
+
+    if let Some(problem_id) = req.path.strip_prefix("/api/problems/") {
+        let Ok(problem_id) = problem_id.parse::<i32>() else {
+            return resp.code("400 Bad Request").body("bad problem id");
+        };
+        let Some(isl) = multipart_file(req.body) else {
+            return resp.code("400 Bad Request").body("no file");
+        };
+        let mut state = state.lock().unwrap();
+        state.submissions.push(MockSubmission { problem_id, isl, polls: 0 });
+        let submission_id = FIRST_SUBMISSION_ID + state.submissions.len() as i32 - 1;
+        return json(resp, serde_json::json!({ "submission_id": submission_id }));
+    }
+
+    if let Some(submission_id) = req.path.strip_prefix("/api/submissions/") {
+        let Ok(submission_id) = submission_id.parse::<i32>() else {
+            return resp.code("400 Bad Request").body("bad submission id");
+        };
+        let mut state = state.lock().unwrap();
+        let pending_polls = state.pending_polls;
+        let scorer = state.scorer;
+        let Some(s) = state.submissions.get_mut((submission_id - FIRST_SUBMISSION_ID) as usize) else {
+            return resp.code("404 Not Found").body("no such submission");
+        };
+        s.polls += 1;
+        let file_url = format!("http://mock/{}.isl", submission_id);
+        if s.polls <= pending_polls {
+            return json(resp, serde_json::json!({ "status": "PROCESSING", "file_url": file_url }));
+        }
+        return match scorer(s.problem_id, &s.isl) {
+            Ok(cost) => json(resp, serde_json::json!({ "status": "SUCCEEDED", "cost": cost, "file_url": file_url })),
+            Err(error) => json(resp, serde_json::json!({ "status": "FAILED", "error": error, "file_url": file_url })),
+        };
+    }
+
+    resp.code("404 Not Found").body("not found")
+}
+
+// Content of the first part of multipart/form-data body.
+// The boundary is taken from the body itself, because the server doesn't expose headers.
+fn multipart_file(body: &[u8]) -> Option<String> {
+    let body = std::str::from_utf8(body).ok()?;
+    let (boundary, rest) = body.split_once("\r\n")?;
+    let (_headers, rest) = rest.split_once("\r\n\r\n")?;
+    let (content, _) = rest.split_once(&format!("\r\n{}", boundary))?;
+    Some(content.to_owned())
+}
+
+#[test]
+fn test_bad_requests() {
+    let mock = MockServer::start();
+    let status = |r: Result<ureq::Response, ureq::Error>| match r {
+        Ok(r) => r.status(),
+        Err(ureq::Error::Status(code, _)) => code,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(status(ureq::get(&mock.conf.url(&["problems", "1"])).call()), 405);
+    assert_eq!(status(ureq::post(&mock.conf.url(&["problems"])).call()), 405);
+    assert_eq!(status(ureq::post(&mock.conf.url(&["problems", "x"])).send_bytes(b"")), 400);
+    assert_eq!(status(ureq::get(&mock.conf.url(&["submissions", "x"])).call()), 400);
+    assert_eq!(status(ureq::get(&mock.conf.url(&["submissions", "1"])).call()), 404);
+    // still serving
+    assert_eq!(status(ureq::get(&mock.conf.url(&["problems"])).call()), 200);
+}
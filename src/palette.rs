@@ -0,0 +1,335 @@
+// Palette quantization: median cut, octree, and a cost-aware palette size selection.
+// See also color_util::k_means().
+
+use fxhash::FxHashMap as HashMap;
+
+use crate::basic::*;
+use crate::image::Image;
+use crate::color_util::{color_freqs, dist_to_color_freqs, optimal_color_for_color_freqs};
+
+// Result of quantization that solvers can consume:
+// the palette and the index of the palette color for every pixel.
+#[derive(Clone, Debug)]
+pub struct PaletteMap {
+    pub palette: Vec<Color>,
+    pub width: i32,
+    pub height: i32,
+    assignment: Vec<usize>,
+}
+
+impl PaletteMap {
+    pub fn new(img: &Image, palette: Vec<Color>) -> PaletteMap {
+        assert!(!palette.is_empty());
+        let mut cache: HashMap<Color, usize> = HashMap::default();
+        let mut assignment = Vec::with_capacity((img.width * img.height) as usize);
+        for y in 0..img.height {
+            for x in 0..img.width {
+                let c = img.get_pixel(x, y);
+                let idx = *cache.entry(c).or_insert_with(|| nearest(&palette, c));
+                assignment.push(idx);
+            }
+        }
+        PaletteMap {
+            palette,
+            width: img.width,
+            height: img.height,
+            assignment,
+        }
+    }
+
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        self.assignment[(y * self.width + x) as usize]
+    }
+
+    pub fn color(&self, x: i32, y: i32) -> Color {
+        self.palette[self.index(x, y)]
+    }
+
+    pub fn counts(&self) -> Vec<usize> {
+        let mut res = vec![0; self.palette.len()];
+        for &i in &self.assignment {
+            res[i] += 1;
+        }
+        res
+    }
+
+    pub fn to_image(&self) -> Image {
+        let mut img = Image::new(self.width, self.height, Color::default());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                img.set_pixel(x, y, self.color(x, y));
+            }
+        }
+        img
+    }
+}
+
+fn nearest(palette: &[Color], c: Color) -> usize {
+    (0..palette.len())
+        .min_by(|&i, &j| c.dist(&palette[i]).partial_cmp(&c.dist(&palette[j])).unwrap())
+        .unwrap()
+}
+
+// Splits the box with the largest weighted extent at the weighted median
+// of its widest channel, until there are enough boxes.
+pub fn median_cut(color_freqs: &HashMap<Color, f64>, num_colors: usize) -> Vec<Color> {
+    let _t = crate::stats_timer!("median_cut").time_it();
+    assert!(num_colors > 0);
+    let mut boxes: Vec<Vec<(Color, f64)>> = vec![color_freqs.iter().map(|(&c, &f)| (c, f)).collect()];
+
+    fn widest_channel(b: &[(Color, f64)]) -> (usize, u8) {
+        (0..4).map(|ch| {
+            let lo = b.iter().map(|(c, _)| c.0[ch]).min().unwrap();
+            let hi = b.iter().map(|(c, _)| c.0[ch]).max().unwrap();
+            (ch, hi - lo)
+        }).max_by_key(|&(_, range)| range).unwrap()
+    }
+
+    while boxes.len() < num_colors {
+        let candidate = boxes.iter().enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let weight: f64 = b.iter().map(|(_, f)| f).sum();
+                (i, widest_channel(b).1 as f64 * weight)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let Some((i, _)) = candidate else { break };
+
+        let mut b = boxes.swap_remove(i);
+        let (ch, _) = widest_channel(&b);
+        b.sort_by_key(|(c, _)| c.0[ch]);
+        let total: f64 = b.iter().map(|(_, f)| f).sum();
+        let mut acc = 0.0;
+        let mut split = 1;
+        for (k, (_, f)) in b.iter().enumerate() {
+            acc += f;
+            if acc >= total / 2.0 {
+                split = (k + 1).clamp(1, b.len() - 1);
+                break;
+            }
+        }
+        let upper = b.split_off(split);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|b| {
+        let cf: HashMap<Color, f64> = b.iter().cloned().collect();
+        optimal_color_for_color_freqs(&cf)
+    }).collect()
+}
+
+struct OctreeNode {
+    children: [Option<usize>; 16],
+    level: usize,
+    weight: f64,
+    sum: [f64; 4],
+    leaf: bool,
+}
+
+// Classic octree quantization, except that the tree branches on one bit
+// of each of the four channels, so every node has up to 16 children.
+pub fn octree(color_freqs: &HashMap<Color, f64>, num_colors: usize) -> Vec<Color> {
+    let _t = crate::stats_timer!("octree").time_it();
+    assert!(num_colors > 0);
+    const DEPTH: usize = 8;
+    let mut nodes = vec![OctreeNode {
+        children: [None; 16],
+        level: 0,
+        weight: 0.0,
+        sum: [0.0; 4],
+        leaf: false,
+    }];
+    for (&c, &f) in color_freqs {
+        let mut cur = 0;
+        for level in 0..=DEPTH {
+            let node = &mut nodes[cur];
+            node.weight += f;
+            for ch in 0..4 {
+                node.sum[ch] += c.0[ch] as f64 * f;
+            }
+            if level == DEPTH {
+                node.leaf = true;
+                break;
+            }
+            let bit = 7 - level;
+            let idx = (0..4).map(|ch| (((c.0[ch] >> bit) & 1) as usize) << ch).sum::<usize>();
+            cur = match nodes[cur].children[idx] {
+                Some(child) => child,
+                None => {
+                    nodes.push(OctreeNode {
+                        children: [None; 16],
+                        level: level + 1,
+                        weight: 0.0,
+                        sum: [0.0; 4],
+                        leaf: false,
+                    });
+                    let child = nodes.len() - 1;
+                    nodes[cur].children[idx] = Some(child);
+                    child
+                }
+            };
+        }
+    }
+
+    // Fold nodes bottom-up, lightest first, so that by the time a level
+    // is processed all its children are already leaves.
+    let mut num_leaves = nodes.iter().filter(|n| n.leaf).count();
+    'levels: for level in (0..DEPTH).rev() {
+        let mut at_level: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].level == level).collect();
+        at_level.sort_by(|&i, &j| nodes[i].weight.partial_cmp(&nodes[j].weight).unwrap());
+        for i in at_level {
+            if num_leaves <= num_colors {
+                break 'levels;
+            }
+            let children: Vec<usize> = nodes[i].children.iter().flatten().cloned().collect();
+            for &ch in &children {
+                nodes[ch].leaf = false;
+            }
+            nodes[i].children = [None; 16];
+            nodes[i].leaf = true;
+            num_leaves = num_leaves + 1 - children.len();
+        }
+    }
+
+    nodes.iter().filter(|n| n.leaf).map(|n| {
+        let mut c = [0u8; 4];
+        for (v, sum) in c.iter_mut().zip(n.sum) {
+            *v = (sum / n.weight).round() as u8;
+        }
+        Color(c)
+    }).collect()
+}
+
+// Lloyd iterations with geometric median centers,
+// because that's what the image distance is measuring.
+pub fn refine_palette(color_freqs: &HashMap<Color, f64>, palette: &mut Vec<Color>, iterations: usize) {
+    let _t = crate::stats_timer!("refine_palette").time_it();
+    for _ in 0..iterations {
+        let mut clusters: Vec<HashMap<Color, f64>> = vec![HashMap::default(); palette.len()];
+        for (&c, &f) in color_freqs {
+            *clusters[nearest(palette, c)].entry(c).or_default() += f;
+        }
+        let mut changed = false;
+        for (center, cluster) in palette.iter_mut().zip(&clusters) {
+            if cluster.is_empty() {
+                continue;
+            }
+            let c = optimal_color_for_color_freqs(cluster);
+            changed |= c != *center;
+            *center = c;
+        }
+        if !changed {
+            break;
+        }
+    }
+    // Drop colors that ended up with no pixels, and duplicates.
+    let mut used: Vec<Color> = color_freqs.keys().map(|&c| palette[nearest(palette, c)]).collect();
+    used.sort();
+    used.dedup();
+    *palette = used;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    MedianCut,
+    Octree,
+}
+
+pub struct PaletteFit {
+    pub map: PaletteMap,
+    pub dist: f64,
+    pub moves_cost: i64,
+}
+
+impl PaletteFit {
+    pub fn score(&self) -> f64 {
+        self.dist + self.moves_cost as f64
+    }
+}
+
+// Picks the palette size by trading the cost of the color moves
+// against the distance saved by having one more color.
+// Each palette color is assumed to need at least one ColorMove
+// on a block the size of the area that color covers.
+pub fn optimize_palette(problem: &Problem, method: Method, max_colors: usize) -> PaletteFit {
+    let _t = crate::stats_timer!("optimize_palette").time_it();
+    let cf = color_freqs(&problem.target, &problem.shape());
+
+    let mut best: Option<PaletteFit> = None;
+    for num_colors in 1..=max_colors {
+        let mut palette = match method {
+            Method::MedianCut => median_cut(&cf, num_colors),
+            Method::Octree => octree(&cf, num_colors),
+        };
+        refine_palette(&cf, &mut palette, 5);
+
+        let mut clusters: Vec<HashMap<Color, f64>> = vec![HashMap::default(); palette.len()];
+        for (&c, &f) in &cf {
+            *clusters[nearest(&palette, c)].entry(c).or_default() += f;
+        }
+        let mut dist = 0.0;
+        let mut moves_cost = 0;
+        for (&color, cluster) in palette.iter().zip(&clusters) {
+            dist += dist_to_color_freqs(cluster, color);
+            let area: f64 = cluster.values().sum();
+            moves_cost += problem.cost(problem.base_costs.color, (area as i32).max(1));
+        }
+        let score = dist + moves_cost as f64;
+        if best.as_ref().map(|b| score < b.score()).unwrap_or(true) {
+            best = Some(PaletteFit {
+                map: PaletteMap::new(&problem.target, palette),
+                dist,
+                moves_cost,
+            });
+        }
+    }
+    best.unwrap()
+}
+
+crate::entry_point!("palette_demo", palette_demo);
+fn palette_demo() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: String = pargs.value_from_str("--problem").unwrap();
+    let max_colors: usize = pargs.value_from_str("--max-colors").unwrap_or(16);
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    for problem_id in crate::util::parse_range(&problems) {
+        let problem = Problem::load(problem_id);
+        for method in [Method::MedianCut, Method::Octree] {
+            let fit = optimize_palette(&problem, method, max_colors);
+            eprintln!("problem {:>2} {:>9?}: {:>2} colors, dist {:.0} + moves {} = {:.0}, smallest color covers {} px",
+                problem_id, method, fit.map.palette.len(), fit.dist, fit.moves_cost, fit.score(),
+                fit.map.counts().iter().min().unwrap());
+            let path = format!("outputs/palette_{}_{:?}.png", problem_id, method);
+            fit.map.to_image().save(&crate::util::project_path(path));
+        }
+    }
+    eprintln!("{}", crate::stats::STATS.render());
+}
+
+#[cfg(test)]
+fn two_color_image() -> Image {
+    let red = Color([200, 10, 10, 255]);
+    let blue = Color([10, 10, 200, 255]);
+    let mut img = Image::new(40, 40, red);
+    img.fill_rect(Shape { x1: 0, y1: 0, x2: 40, y2: 10 }, blue);
+    img
+}
+
+#[test]
+fn test_quantization_finds_both_colors() {
+    let img = two_color_image();
+    let cf = color_freqs(&img, &Shape::from_image(&img));
+    for mut palette in [median_cut(&cf, 2), octree(&cf, 2)] {
+        palette.sort();
+        assert_eq!(palette, vec![Color([10, 10, 200, 255]), Color([200, 10, 10, 255])]);
+
+        let map = PaletteMap::new(&img, palette);
+        assert_eq!(map.to_image(), img);
+        let mut counts = map.counts();
+        counts.sort();
+        assert_eq!(counts, vec![400, 1200]);
+    }
+}
@@ -1,7 +1,7 @@
 crate::entry_point!("print_invocations_demo", print_invocations_demo);
 fn print_invocations_demo() {
-    let mut client = crate::db::create_client();
-    for snap in crate::invocation::get_invocations(&mut client) {
+    let mut store = crate::store::open();
+    for snap in crate::invocation::get_invocations(&mut *store) {
         println!("({:?}) {:#?}", snap.status, snap.invocation);
     }
 }
@@ -0,0 +1,182 @@
+// Compares the server's cost of successful submissions with our score.
+// We rely on the local scorer being exact, so any difference means
+// our reading of the cost rules is off somewhere. To find where, the solution
+// is replayed under a few plausible alternative readings, and the first move
+// where they differ from ours is reported.
+
+use crate::basic::*;
+use crate::invocation::InvocationGuard;
+use crate::store::{ScoreMismatch, SubmissionResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    FloorCost,
+    CeilCost,
+    MergeSmallerSize,
+    MergeResultSize,
+    FloorDistance,
+    CeilDistance,
+}
+
+impl Rule {
+    // No swap rules, swapped blocks are the same size, so there is nothing to misread.
+    const ALL: [Rule; 6] = [
+        Rule::FloorCost, Rule::CeilCost,
+        Rule::MergeSmallerSize, Rule::MergeResultSize,
+        Rule::FloorDistance, Rule::CeilDistance,
+    ];
+
+    fn description(self) -> &'static str {
+        match self {
+            Rule::FloorCost => "move cost rounded down",
+            Rule::CeilCost => "move cost rounded up",
+            Rule::MergeSmallerSize => "merge cost uses the smaller block",
+            Rule::MergeResultSize => "merge cost uses the merged block",
+            Rule::FloorDistance => "image distance rounded down",
+            Rule::CeilDistance => "image distance rounded up",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub rule: Rule,
+    pub first_move: Option<usize>,  // None for image distance rules
+    pub delta: i64,  // score under the rule minus our score
+}
+
+// How the score would change under each alternative rule, for rules that change anything.
+pub fn divergences(problem: &Problem, moves: &[Move]) -> Vec<Divergence> {
+    let area = (problem.width * problem.height) as i64;
+    let round = |base: i64, size: i64| (base * area + (size + 1) / 2) / size;
+    let floor = |base: i64, size: i64| base * area / size;
+    let ceil = |base: i64, size: i64| (base * area + size - 1) / size;
+
+    let mut res: Vec<Divergence> = Rule::ALL.iter()
+        .map(|&rule| Divergence { rule, first_move: None, delta: 0 })
+        .collect();
+    let mut painter = PainterState::new(problem);
+    for (i, m) in moves.iter().enumerate() {
+        let size = |id: &BlockId| painter.blocks[id].shape.size() as i64;
+        let bc = &problem.base_costs;
+        let (base, sizes) = match m {
+            Move::PCut { block_id, .. } => (bc.pcut, [size(block_id), 0]),
+            Move::LCut { block_id, .. } => (bc.lcut, [size(block_id), 0]),
+            Move::ColorMove { block_id, .. } => (bc.color, [size(block_id), 0]),
+            Move::Swap { block_id1, .. } => (bc.swap, [size(block_id1), 0]),
+            Move::Merge { block_id1, block_id2 } => (bc.merge, [size(block_id1), size(block_id2)]),
+        };
+        let our_size = match m {
+            Move::Merge { .. } => sizes[0].max(sizes[1]),
+            _ => sizes[0],
+        };
+        let ours = painter.apply_move(m).cost;
+        for d in &mut res {
+            let alt = match (d.rule, m) {
+                (Rule::FloorCost, _) => floor(base, our_size),
+                (Rule::CeilCost, _) => ceil(base, our_size),
+                (Rule::MergeSmallerSize, Move::Merge { .. }) => round(base, sizes[0].min(sizes[1])),
+                (Rule::MergeResultSize, Move::Merge { .. }) => round(base, sizes[0] + sizes[1]),
+                _ => ours,
+            };
+            if alt != ours && d.first_move.is_none() {
+                d.first_move = Some(i);
+            }
+            d.delta += alt - ours;
+        }
+    }
+    let dist = image_distance(&painter.render(), &problem.target);
+    for d in &mut res {
+        match d.rule {
+            Rule::FloorDistance => d.delta = dist.floor() as i64 - dist.round() as i64,
+            Rule::CeilDistance => d.delta = dist.ceil() as i64 - dist.round() as i64,
+            _ => {}
+        }
+    }
+    res.retain(|d| d.delta != 0 || d.first_move.is_some());
+    res
+}
+
+// (suspect move, explanation) for the observed difference their_score - our_score.
+pub fn explain(moves: &[Move], divergences: &[Divergence], delta: i64) -> (Option<i32>, String) {
+    let describe = |d: &Divergence| match d.first_move {
+        Some(i) => format!("{} (first at move {}: {}, total {:+})", d.rule.description(), i, moves[i], d.delta),
+        None => format!("{} ({:+})", d.rule.description(), d.delta),
+    };
+    if let Some(d) = divergences.iter().find(|d| d.delta == delta) {
+        return (d.first_move.map(|i| i as i32), format!("explained by: {}", describe(d)));
+    }
+    let suspect = divergences.iter().filter_map(|d| d.first_move).min();
+    let candidates: Vec<String> = divergences.iter().map(describe).collect();
+    let explanation = if candidates.is_empty() {
+        "unexplained, no alternative rule changes anything".to_owned()
+    } else {
+        format!("unexplained, candidates: {}", candidates.join("; "))
+    };
+    (suspect.map(|i| i as i32), explanation)
+}
+
+crate::entry_point!("reconcile_scores", reconcile_scores);
+fn reconcile_scores() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
+
+    let mut store = crate::store::open();
+    let mut mismatches = vec![];
+    let mut num_checked = 0;
+    for sub in store.submissions() {
+        let SubmissionResult::Good { cost: their_score, .. } = sub.result else { continue };
+        num_checked += 1;
+        let s = store.solution(sub.solution_id);
+        if s.score == their_score {
+            continue;
+        }
+        let moves = Move::parse_many(&store.solution_data(s.id));
+        let problem = Problem::load(s.problem_id);
+        let (suspect_move, explanation) = explain(&moves, &divergences(&problem, &moves), their_score - s.score);
+        eprintln!("submission {} (problem {}, solution/{}): ours {}, theirs {} ({:+}), {}",
+            sub.submission_id, s.problem_id, s.id, s.score, their_score, their_score - s.score, explanation);
+        mismatches.push(ScoreMismatch {
+            submission_id: sub.submission_id,
+            solution_id: s.id,
+            our_score: s.score,
+            their_score,
+            suspect_move,
+            explanation,
+            invocation_id: invocation.id(),
+            timestamp: chrono::Utc::now(),
+        });
+    }
+    eprintln!("checked {} successful submissions, {} mismatches", num_checked, mismatches.len());
+    if dry_run {
+        eprintln!("But not recording them, because it was a --dry-run!");
+        return;
+    }
+    let mut tx = store.transaction();
+    for m in &mismatches {
+        tx.record_score_mismatch(m);
+    }
+    tx.commit();
+}
+
+#[test]
+fn test_divergences() {
+    let problem = Problem::load(1);
+    let moves = Move::parse_many("
+        cut [0] [x] [100]
+        merge [0.0] [0.1]
+    ");
+    let divs = divergences(&problem, &moves);
+    let smaller = divs.iter().find(|d| d.rule == Rule::MergeSmallerSize).unwrap();
+    assert_eq!(smaller.first_move, Some(1));
+    assert_eq!(smaller.delta, 4 - 1);  // 160000 / 40000 instead of 160000 / 120000
+
+    let (suspect, explanation) = explain(&moves, &divs, 3);
+    assert_eq!(suspect, Some(1));
+    assert!(explanation.contains("smaller block"), "{}", explanation);
+    let (_, explanation) = explain(&moves, &divs, 1000);
+    assert!(explanation.starts_with("unexplained"), "{}", explanation);
+}
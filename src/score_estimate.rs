@@ -0,0 +1,145 @@
+// Cheap estimates of the best possible score, to see how far the best solutions are from optimal.
+//
+// They are lower bounds only for solutions without swaps (see grid_bound()),
+// so a solution that uses them can score below the estimate.
+// Also they rely on optimal_color_for_color_freqs() finding the best color,
+// which is not guaranteed, so in theory they can be off by a little.
+
+use crate::basic::*;
+use crate::color_util::{color_freqs, dist_to_color_freqs, optimal_color_for_color_freqs};
+
+#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ScoreEstimate {
+    pub estimate: i64,
+    // Which bound turned out the strongest, "trivial", "single_block" or "grid NxN (no swaps)".
+    pub kind: String,
+}
+
+fn best_single_color_dist(problem: &Problem, shape: Shape) -> f64 {
+    let cf = color_freqs(&problem.target, &shape);
+    dist_to_color_freqs(&cf, optimal_color_for_color_freqs(&cf))
+}
+
+// Starting from a single block, the first move is either a color move
+// or a cut of the whole canvas. Without cuts, the picture stays uniform.
+fn single_block_bound(problem: &Problem, start: &PainterState) -> i64 {
+    let canvas = problem.shape();
+    let no_moves = image_distance(&problem.target, &start.render());
+    let one_color = problem.cost(problem.base_costs.color, canvas.size()) as f64
+        + best_single_color_dist(problem, canvas);
+    let any_cut = problem.cost(problem.base_costs.lcut.min(problem.base_costs.pcut), canvas.size()) as f64;
+    no_moves.min(one_color).min(any_cut).floor() as i64
+}
+
+// Splits the canvas into an n x n grid of tiles.
+// A tile that isn't uniform in the final picture must be crossed by a cut line,
+// and a line of any cut crosses at most 2 * n tiles (a point cut, n for a line cut).
+// Each cut costs at least its base cost, since no block is bigger than the canvas.
+// So if k tiles are not uniform, that takes at least ceil(k / (2 * n)) cuts,
+// and every uniform tile contributes at least its best single color distance.
+// This ignores swaps, which can move pieces of the already painted lines around,
+// so it only holds for solutions without them. Accounting for that would need a bound
+// on how many tiles a swap can spoil, and a swapped block can carry any number of lines.
+fn grid_bound(problem: &Problem, n: i32) -> i64 {
+    assert!(problem.width % n == 0 && problem.height % n == 0);
+    let (tw, th) = (problem.width / n, problem.height / n);
+    let mut tile_dists = vec![];
+    for i in 0..n {
+        for j in 0..n {
+            let shape = Shape { x1: i * tw, y1: j * th, x2: (i + 1) * tw, y2: (j + 1) * th };
+            tile_dists.push(best_single_color_dist(problem, shape));
+        }
+    }
+    // The k tiles allowed to be non-uniform are obviously the ones with the largest distances.
+    tile_dists.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    let cut_cost = problem.base_costs.lcut.min(problem.base_costs.pcut);
+    let per_cut = 2 * n as usize;
+
+    let mut rest: f64 = tile_dists.iter().sum();
+    let mut best = rest;
+    for (k, d) in tile_dists.iter().enumerate() {
+        rest -= d;
+        let num_cuts = (k + 1).div_ceil(per_cut);
+        best = best.min(rest + (num_cuts as i64 * cut_cost) as f64);
+    }
+    best.floor() as i64
+}
+
+pub fn score_estimate(problem: &Problem) -> ScoreEstimate {
+    let _t = crate::stats_timer!("score_estimate").time_it();
+    let start = PainterState::new(problem);
+    // With several start blocks (or a bitmap) the picture is non-uniform for free,
+    // and small blocks are cheap to operate on, so there is nothing clever to say.
+    let single_unicolor_block = start.blocks.len() == 1 &&
+        start.blocks.values().all(|b| matches!(b.pieces[..], [(_, Pic::Unicolor(_))]));
+    if !single_unicolor_block {
+        return ScoreEstimate { estimate: 0, kind: "trivial".to_string() };
+    }
+
+    let mut res = ScoreEstimate {
+        estimate: single_block_bound(problem, &start),
+        kind: "single_block".to_string(),
+    };
+    for n in [2, 4, 5, 8, 10, 16, 20, 25, 40] {
+        if problem.width % n != 0 || problem.height % n != 0 {
+            continue;
+        }
+        let bound = grid_bound(problem, n);
+        if bound > res.estimate {
+            res = ScoreEstimate { estimate: bound, kind: format!("grid {}x{} (no swaps)", n, n) };
+        }
+    }
+    res
+}
+
+crate::entry_point!("score_estimates", score_estimates);
+fn score_estimates() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: String = pargs.value_from_str("--problem").unwrap();
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+
+    let mut store = crate::store::open();
+    for problem_id in crate::util::parse_range(&problems) {
+        let problem = Problem::load(problem_id);
+        let est = score_estimate(&problem);
+        eprintln!("problem {:>2}: {:>6} ({})", problem_id, est.estimate, est.kind);
+        if dry_run {
+            continue;
+        }
+        let mut tx = store.transaction();
+        tx.set_score_estimate(problem_id, &est);
+        tx.commit();
+    }
+}
+
+#[test]
+fn test_score_estimate_below_known_solutions() {
+    let problem = Problem::load(1);
+    let est = score_estimate(&problem).estimate;
+    assert!(est > 0);
+
+    let score = |moves: &[Move]| {
+        let mut painter = PainterState::new(&problem);
+        for m in moves {
+            painter.apply_move(m);
+        }
+        painter.cost + image_distance(&problem.target, &painter.render()).round() as i64
+    };
+
+    assert!(est <= score(&[]));
+    let color = optimal_color_for_color_freqs(&color_freqs(&problem.target, &problem.shape()));
+    assert!(est <= score(&[Move::ColorMove { block_id: BlockId::root(0), color }]));
+
+    // Quadrants in their best colors.
+    let mut painter = PainterState::new(&problem);
+    let ids = painter.apply_move(&Move::PCut { block_id: BlockId::root(0), x: 200, y: 200 }).new_block_ids;
+    for id in ids {
+        let shape = painter.blocks[&id].shape;
+        let color = optimal_color_for_color_freqs(&color_freqs(&problem.target, &shape));
+        painter.apply_move(&Move::ColorMove { block_id: id, color });
+    }
+    assert!(est <= score(&painter.moves));
+}
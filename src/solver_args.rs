@@ -0,0 +1,99 @@
+// solver_args column is JSON, but every solver that uploads non-null args
+// registers their type here, with a version that is stored along with the solution:
+//
+//     #[derive(serde::Serialize, serde::Deserialize)]
+//     struct SolverArgs { ... }
+//     crate::solver_args!(SolverArgs, "my solver", 1);
+//
+// upload_solution() checks that the args parse as the latest registered version.
+// When the struct changes incompatibly, bump the version
+// (and keep the old struct registered if old args still have to be read).
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::store::{SolutionRow, Store};
+
+pub trait SolverArgs: Serialize + DeserializeOwned {
+    const SOLVER: &'static str;
+    const VERSION: i32;
+}
+
+pub struct Schema {
+    pub solver: &'static str,
+    pub version: i32,
+    pub validate: fn(&serde_json::Value) -> Result<(), String>,
+}
+
+#[linkme::distributed_slice]
+pub static SCHEMAS: [Schema] = [..];
+
+#[macro_export]
+macro_rules! solver_args {
+    ($t:ty, $solver:expr, $version:expr) => {
+        $crate::solver_args!($t, $solver, $version, _SOLVER_ARGS);
+    };
+    ($t:ty, $solver:expr, $version:expr, $static_name:ident) => {
+        impl $crate::solver_args::SolverArgs for $t {
+            const SOLVER: &'static str = $solver;
+            const VERSION: i32 = $version;
+        }
+        #[linkme::distributed_slice($crate::solver_args::SCHEMAS)]
+        static $static_name: $crate::solver_args::Schema = $crate::solver_args::Schema {
+            solver: $solver,
+            version: $version,
+            validate: |v| serde_json::from_value::<$t>(v.clone()).map(|_| ()).map_err(|e| e.to_string()),
+        };
+    };
+}
+
+// Returns the version to store with the solution,
+// None for solvers without registered args (their args have to be null).
+pub fn validate(solver: &str, args: &serde_json::Value) -> Option<i32> {
+    let Some(schema) = SCHEMAS.iter().filter(|s| s.solver == solver).max_by_key(|s| s.version) else {
+        assert!(args.is_null(), "args of solver {:?} are not registered, see solver_args.rs: {}", solver, args);
+        return None;
+    };
+    if let Err(e) = (schema.validate)(args) {
+        panic!("invalid args for solver {:?} v{}: {}: {}", solver, schema.version, e, args);
+    }
+    Some(schema.version)
+}
+
+// Solutions by T::SOLVER together with their args, ordered by timestamp.
+// Solutions uploaded before versioning are included if their args parse.
+pub fn solutions_with_args<T: SolverArgs>(store: &mut dyn Store, problem_id: Option<i32>) -> Vec<(SolutionRow, T)> {
+    store.solutions(problem_id).into_iter()
+        .filter(|s| s.solver_name == T::SOLVER)
+        .filter(|s| s.solver_args_version.is_none_or(|v| v == T::VERSION))
+        .filter_map(|s| {
+            let args = serde_json::from_value(s.solver_args.clone()).ok()?;
+            Some((s, args))
+        })
+        .collect()
+}
+
+// For warm starts.
+pub fn best_args<T: SolverArgs>(store: &mut dyn Store, problem_id: i32) -> Option<(SolutionRow, T)> {
+    solutions_with_args::<T>(store, Some(problem_id)).into_iter()
+        .min_by_key(|(s, _)| (s.score, s.timestamp))
+}
+
+#[test]
+fn schemas_unique() {
+    for (i, s) in SCHEMAS.iter().enumerate() {
+        for s2 in &SCHEMAS[..i] {
+            assert!(s.solver != s2.solver || s.version != s2.version,
+                "duplicate solver args {:?} v{}", s.solver, s.version);
+        }
+    }
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(validate("no such solver", &serde_json::Value::Null), None);
+    let version = validate("reuse", &serde_json::json!({ "granularity": 20 }));
+    assert!(version.is_some());
+    let res = std::panic::catch_unwind(|| validate("reuse", &serde_json::json!({ "granularity": "x" })));
+    assert!(res.is_err());
+}
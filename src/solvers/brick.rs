@@ -9,19 +9,19 @@ use rand::prelude::*;
 use crate::util::project_path;
 use crate::basic::*;
 use crate::image::Image;
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 use crate::color_util::*;
 use crate::seg_util;
 use crate::transform::Transformation::TransposeXY;
 use crate::transform::transform_solution;
+use crate::store::Derivation;
 
 use crate::basic::Move::*;
 
 struct Shared {
-    client: postgres::Client,
     best_scores: BTreeMap<i32, i64>,
-    improvements: HashMap<i32, (SolverArgs, Vec<Move>)>,
+    improvements: HashMap<i32, (SolverArgs, Vec<Move>, Option<i32>)>,  // (args, moves, parent solution)
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -31,6 +31,7 @@ struct SolverArgs {
     granularity: i32,
     ys: Vec<i32>,
 }
+crate::solver_args!(SolverArgs, "brick DP", 1);
 
 crate::entry_point!("brick_solver", brick_solver, _EP1);
 fn brick_solver() {
@@ -45,34 +46,33 @@ fn brick_solver() {
 
     assert_eq!(400 % granularity, 0);
 
-    let mut client = crate::db::create_client();
-    let rows = client.query("SELECT problem_id, moves_cost + image_distance AS score, solver_args FROM solutions", &[]).unwrap();
+    let mut store = crate::store::open();
     let mut best_scores: BTreeMap<i32, i64> = problem_range.clone().map(|i| (i, i64::MAX)).collect();
-    let mut best_solver_args: HashMap<i32, SolverArgs> = HashMap::default();
-    for row in rows {
-        let problem_id: i32 = row.get("problem_id");
-        let score: i64 = row.get("score");
-        let postgres::types::Json::<serde_json::Value>(sa) = row.get("solver_args");
-
-        if let Some(e) = best_scores.get_mut(&problem_id) {
-            *e = (*e).min(score);
-
-            if let Ok(sa) = serde_json::from_value::<SolverArgs>(sa) {
-                best_solver_args.insert(problem_id, sa);
-            }
+    for row in store.solutions(None) {
+        if let Some(e) = best_scores.get_mut(&row.problem_id) {
+            *e = (*e).min(row.score);
+        }
+    }
+    let mut best_solver_args: HashMap<i32, (i32, SolverArgs)> = HashMap::default();
+    for problem_id in problem_range.clone() {
+        if let Some((row, sa)) = crate::solver_args::best_args::<SolverArgs>(&mut *store, problem_id) {
+            best_solver_args.insert(problem_id, (row.id, sa));
         }
     }
+    // The submitter thread opens its own, see db::Pool.
+    drop(store);
     eprintln!("{:?}", best_scores);
     eprintln!("{:?}", best_solver_args);
 
     let local_best_scores: BTreeMap<i32, AtomicI64> = problem_range.clone().map(|i| (i, AtomicI64::new(i64::MAX))).collect();
     let shared = Shared {
-        client,
         best_scores,
         improvements: HashMap::default(),
     };
     let shared = Mutex::new(shared);
 
+    let invocation = InvocationGuard::start();
+    let invocation_id = invocation.id();
     std::thread::scope(|scope| {
         // imrovement submitter thread
         scope.spawn(|| {
@@ -81,18 +81,19 @@ fn brick_solver() {
                 std::thread::sleep(std::time::Duration::from_secs(60));
                 let shared = &mut *shared.lock().unwrap();
                 eprintln!("submitting {} improvements", shared.improvements.len());
-                let mut tx = shared.client.transaction().unwrap();
-                let incovation_id = record_this_invocation(&mut tx, Status::KeepRunning { seconds: 65.0 });
+                let mut store = crate::store::open();
+                let mut tx = store.transaction();
                 if !shared.improvements.is_empty() {
-                    for (problem_id, (solver_args, moves)) in shared.improvements.drain() {
-                        if dry_run {
-                            eprintln!("dry run: pretend submit improvement for problem {}", problem_id);
-                        } else {
-                            upload_solution(&mut tx, problem_id, &moves, "brick DP", &serde_json::to_value(&solver_args).unwrap(), incovation_id);
-                        }
+                    for (problem_id, (solver_args, moves, parent_id)) in shared.improvements.drain() {
+                        let derivation = parent_id.map(|parent_id| Derivation { parent_id, label: "brick --start-from-best" });
+                        upload_solution(&mut *tx, problem_id, &moves, "brick DP", &serde_json::to_value(&solver_args).unwrap(), invocation_id, derivation);
                     }
                 }
-                tx.commit().unwrap();
+                if dry_run {
+                    eprintln!("But not really, because it was a --dry-run!");
+                } else {
+                    tx.commit();
+                }
                 eprintln!("{}", crate::stats::STATS.render());
 
                 for (problem_id, best_score) in &shared.best_scores {
@@ -122,10 +123,12 @@ fn brick_solver() {
                     let (_, initial_moves) = seg_util::merge_all(&mut painter);
                     let initial_cost = painter.cost;
                     let mut best_ys = random_seps();
+                    let mut parent_id = None;
                     if start_from_best {
-                        if let Some(sa) = best_solver_args.get(&problem_id) {
+                        if let Some((id, sa)) = best_solver_args.get(&problem_id) {
                             if sa.transposed == transposed {
                                 best_ys = sa.ys.clone();
+                                parent_id = Some(*id);
                                 eprintln!("problem {}: start from best ys {:?}", problem_id, best_ys);
                             } else {
                                 return;
@@ -179,7 +182,7 @@ fn brick_solver() {
                                     granularity,
                                     ys: ys.clone(),
                                 };
-                                shared.improvements.insert(problem_id, (a, moves));
+                                shared.improvements.insert(problem_id, (a, moves, parent_id));
                             }
                         }
                     }
@@ -13,7 +13,7 @@
 use crate::util::project_path;
 use crate::basic::*;
 use crate::image::Image;
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 use crate::color_util::*;
 use crate::seg_util;
@@ -35,21 +35,20 @@ fn dummy_solver() {
     let dry_run = pargs.contains("--dry-run");
     let rest = pargs.finish();
     assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
 
     let problem_range = crate::util::parse_range(&problems);
-    let mut client = crate::db::create_client();
+    let mut store = crate::store::open();
     for problem_id in problem_range {
         eprintln!("*********** problem {} ***********", problem_id);
         let problem = Problem::load(problem_id);
         let moves = solve(&problem);
-        let mut tx = client.transaction().unwrap();
-        let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-        upload_solution(&mut tx, problem_id, &moves, "dummy", &serde_json::Value::Null, incovation_id);
+        let mut tx = store.transaction();
+        upload_solution(&mut *tx, problem_id, &moves, "dummy", &serde_json::Value::Null, invocation.id(), None);
         if dry_run {
             eprintln!("But not really, because it was a --dry-run!");
-            break;  // because record_this_invocation() doesn't play well with reverted transactions
         } else {
-            tx.commit().unwrap();
+            tx.commit();
         }
     }
 }
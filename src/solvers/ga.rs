@@ -7,8 +7,8 @@ use crate::{color_util, seg_util};
 use rand::prelude::*;
 use rand::rngs::ThreadRng;
 use crate::image::Image;
-use crate::invocation::{record_this_invocation, Status};
-use crate::uploader::{best_solution, upload_solution};
+use crate::invocation::InvocationGuard;
+use crate::uploader::upload_solution;
 
 crate::entry_point!("ga_solver", ga_solver);
 
@@ -558,10 +558,11 @@ fn ga_solver() {
         std::process::exit(1);
     }
     let problem_id: i32 = args[0].parse().unwrap();
+    let invocation = InvocationGuard::start();
     let problem = Problem::load(problem_id);
 
-    let mut client = crate::db::create_client();
-    let best = best_solution(&mut client, problem_id).score;
+    let mut store = crate::store::open();
+    let best = store.best_solution(problem_id).map_or(i64::MAX, |s| s.score);
     println!("BEST for {}: {}", problem_id, best);
 
     // let mut state = State::new(problem.clone());
@@ -593,10 +594,9 @@ fn ga_solver() {
 
         if score < best {
             println!("YAY I WON: {} < {}", score, best);
-            let mut tx = client.transaction().unwrap();
-            let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-            upload_solution(&mut tx, problem_id, &moves, "ga", &serde_json::Value::Null, incovation_id);
-            tx.commit().unwrap();
+            let mut tx = store.transaction();
+            upload_solution(&mut *tx, problem_id, &moves, "ga", &serde_json::Value::Null, invocation.id(), None);
+            tx.commit();
         }
 
     };
@@ -6,4 +6,6 @@ mod poke;
 mod ga;
 mod spot;
 mod brick;
-mod raster;
\ No newline at end of file
+mod raster;
+mod quilt;
+mod reuse;
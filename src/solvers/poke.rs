@@ -3,7 +3,7 @@
 use fxhash::FxHashMap as HashMap;
 use crate::basic::*;
 use crate::image::{Image};
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 
 struct State<'a> {
@@ -105,6 +105,7 @@ fn poke_solver() {
         std::process::exit(1);
     }
     let problem_id: i32 = args[0].parse().unwrap();
+    let invocation = InvocationGuard::start();
     let problem = Problem::load(problem_id);
     let mut state = State::new(&problem);
 
@@ -127,9 +128,8 @@ fn poke_solver() {
     img.save(&crate::util::project_path(&output_path));
     eprintln!("saved to {}", output_path);
 
-    let mut client = crate::db::create_client();
-    let mut tx = client.transaction().unwrap();
-    let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-    upload_solution(&mut tx, problem_id, &moves, "poke", &serde_json::Value::Null, incovation_id);
-    tx.commit().unwrap();
+    let mut store = crate::store::open();
+    let mut tx = store.transaction();
+    upload_solution(&mut *tx, problem_id, &moves, "poke", &serde_json::Value::Null, invocation.id(), None);
+    tx.commit();
 }
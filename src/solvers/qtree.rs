@@ -1,7 +1,7 @@
 use crate::image::Image;
 use crate::basic::*;
 use crate::basic::Move::PCut;
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 use crate::color_util::optimal_color_for_block;
 
@@ -108,6 +108,7 @@ fn qtree_solver() {
         std::process::exit(1);
     }
     let problem_id: i32 = args[0].parse().unwrap();
+    let invocation = InvocationGuard::start();
     let problem = Problem::load(problem_id);
 
     let mut painter = PainterState::new(&problem);
@@ -131,9 +132,8 @@ fn qtree_solver() {
     img.save(&crate::util::project_path(&output_path));
     eprintln!("saved to {}", output_path);
 
-    let mut client = crate::db::create_client();
-    let mut tx = client.transaction().unwrap();
-    let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-    upload_solution(&mut tx, problem_id, &moves, "qtree", &serde_json::Value::Null, incovation_id);
-    tx.commit().unwrap();
+    let mut store = crate::store::open();
+    let mut tx = store.transaction();
+    upload_solution(&mut *tx, problem_id, &moves, "qtree", &serde_json::Value::Null, invocation.id(), None);
+    tx.commit();
 }
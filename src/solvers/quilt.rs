@@ -0,0 +1,327 @@
+use fxhash::FxHashMap as HashMap;
+use crate::basic::*;
+use crate::basic::Move::*;
+use crate::image::Image;
+use crate::invocation::InvocationGuard;
+use crate::uploader::upload_solution;
+use crate::color_util::{color_freqs, color_freqs_distance, optimal_color_for_color_freqs};
+use crate::seg_util;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug)]
+struct SolverArgs {
+    threshold: f64,
+    granularity: i32,
+}
+crate::solver_args!(SolverArgs, "quilt", 1);
+
+crate::entry_point!("quilt_solver", quilt_solver);
+fn quilt_solver() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: String = pargs.value_from_str("--problem").unwrap();
+    // Comma-separated list, every value is tried and the best result is uploaded.
+    let thresholds: String = pargs.value_from_str("--threshold").unwrap_or_else(|_| "0,10,20,40".to_owned());
+    let granularity: i32 = pargs.value_from_str("--granularity").unwrap_or(10);
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
+
+    let problem_range = crate::util::parse_range(&problems);
+    let thresholds: Vec<f64> = thresholds.split(',').map(|s| s.parse().unwrap()).collect();
+
+    let mut store = crate::store::open();
+    for problem_id in problem_range {
+        eprintln!("*********** problem {} ***********", problem_id);
+        let problem = Problem::load(problem_id);
+
+        let (args, (total_score, moves)) = thresholds.iter()
+            .map(|&threshold| SolverArgs { threshold, granularity })
+            .map(|args| {
+                let res = solve(&args, &problem);
+                (args, res)
+            })
+            .min_by_key(|(_args, (score, _))| *score).unwrap();
+        eprintln!("BEST:  {:?}: {}", args, total_score);
+
+        let mut tx = store.transaction();
+        upload_solution(&mut *tx, problem_id, &moves, "quilt", &serde_json::to_value(&args).unwrap(), invocation.id(), None);
+        if dry_run {
+            eprintln!("But not really, because it was a --dry-run!");
+        } else {
+            tx.commit();
+        }
+    }
+}
+
+fn solve(args: &SolverArgs, problem: &Problem) -> (i64, Vec<Move>) {
+    let _t = crate::stats_timer!("quilt/solve").time_it();
+    let edges = Edges::new(&problem.target);
+
+    let mut leaves = vec![];
+    partition(problem, &edges, problem.shape(), args.granularity, &mut leaves);
+    eprintln!("  {} leaves", leaves.len());
+
+    let regions = merge_similar(&problem.target, &leaves, args.threshold);
+    eprintln!("  {} regions", regions.len());
+
+    let moves = paint(problem, regions);
+
+    let mut painter = PainterState::new(problem);
+    for m in &moves {
+        painter.apply_move(m);
+    }
+    let total_score = painter.cost + image_distance(&problem.target, &painter.render()).round() as i64;
+    eprintln!("  {:?}: {}", args, total_score);
+    (total_score, moves)
+}
+
+// Prefix sums of color differences between neighboring pixels,
+// so that the strength of any cut line can be looked up in O(1).
+struct Edges {
+    width: i32,
+    height: i32,
+    // vertical[x * (height + 1) + y] is the sum of |p(x - 1, y') - p(x, y')| for y' < y
+    vertical: Vec<f64>,
+    // horizontal[y * (width + 1) + x] is the sum of |p(x', y - 1) - p(x', y)| for x' < x
+    horizontal: Vec<f64>,
+}
+
+impl Edges {
+    fn new(img: &Image) -> Edges {
+        let (w, h) = (img.width, img.height);
+        let mut vertical = vec![0.0; ((w + 1) * (h + 1)) as usize];
+        for x in 1..w {
+            for y in 0..h {
+                let d = img.get_pixel(x - 1, y).dist(&img.get_pixel(x, y));
+                let i = (x * (h + 1) + y) as usize;
+                vertical[i + 1] = vertical[i] + d;
+            }
+        }
+        let mut horizontal = vec![0.0; ((w + 1) * (h + 1)) as usize];
+        for y in 1..h {
+            for x in 0..w {
+                let d = img.get_pixel(x, y - 1).dist(&img.get_pixel(x, y));
+                let i = (y * (w + 1) + x) as usize;
+                horizontal[i + 1] = horizontal[i] + d;
+            }
+        }
+        Edges { width: w, height: h, vertical, horizontal }
+    }
+
+    // Average color jump across the cut line, restricted to the shape.
+    fn strength(&self, shape: Shape, orientation: Orientation, line_number: i32) -> f64 {
+        match orientation {
+            Orientation::Vertical => {
+                let base = line_number * (self.height + 1);
+                let s = self.vertical[(base + shape.y2) as usize] - self.vertical[(base + shape.y1) as usize];
+                s / shape.height() as f64
+            }
+            Orientation::Horizontal => {
+                let base = line_number * (self.width + 1);
+                let s = self.horizontal[(base + shape.x2) as usize] - self.horizontal[(base + shape.x1) as usize];
+                s / shape.width() as f64
+            }
+        }
+    }
+}
+
+fn region_dist(img: &Image, shape: Shape) -> f64 {
+    let cf = color_freqs(img, &shape);
+    let color = optimal_color_for_color_freqs(&cf);
+    color_freqs_distance(&cf, color) * 0.005
+}
+
+// Guillotine partition of the target.
+// Cut lines are taken from the strongest edges. The tree is grown down
+// to the granularity and then pruned bottom-up, a cut is kept only if it
+// pays for itself (including the color moves of the resulting leaves).
+// Returns the estimated score of the subtree.
+fn partition(problem: &Problem, edges: &Edges, shape: Shape, granularity: i32, leaves: &mut Vec<Shape>) -> f64 {
+    const CANDIDATES: usize = 3;
+
+    let own_dist = region_dist(&problem.target, shape);
+    let leaf_score = own_dist + problem.cost(problem.base_costs.color, shape.size()) as f64;
+
+    let mut cuts = vec![];
+    for orientation in [Orientation::Vertical, Orientation::Horizontal] {
+        let (lo, hi) = match orientation {
+            Orientation::Vertical => (shape.x1, shape.x2),
+            Orientation::Horizontal => (shape.y1, shape.y2),
+        };
+        let mut lines: Vec<(f64, i32)> = (lo + 1..hi)
+            .filter(|line| line % granularity == 0)
+            .map(|line| (edges.strength(shape, orientation, line), line))
+            .collect();
+        lines.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        for &(_, line) in lines.iter().take(CANDIDATES) {
+            cuts.push((orientation, line));
+        }
+    }
+
+    let best = cuts.into_iter()
+        .map(|(orientation, line)| {
+            let halves = shape.l_cut_subshapes(orientation, line);
+            let dist = region_dist(&problem.target, halves[0]) + region_dist(&problem.target, halves[1]);
+            (dist, halves)
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    // Uniform regions can't be improved by cutting.
+    let Some((_, halves)) = best.filter(|_| own_dist > 1.0) else {
+        leaves.push(shape);
+        return leaf_score;
+    };
+
+    let checkpoint = leaves.len();
+    let mut split_score = problem.cost(problem.base_costs.lcut, shape.size()) as f64;
+    for half in halves {
+        split_score += partition(problem, edges, half, granularity, leaves);
+    }
+    if split_score < leaf_score {
+        split_score
+    } else {
+        leaves.truncate(checkpoint);
+        leaves.push(shape);
+        leaf_score
+    }
+}
+
+struct Region {
+    shapes: Vec<Shape>,
+    color_freqs: HashMap<Color, f64>,
+    color: Color,
+}
+
+impl Region {
+    fn size(&self) -> i32 {
+        self.shapes.iter().map(|s| s.size()).sum()
+    }
+}
+
+fn adjacent(a: Shape, b: Shape) -> bool {
+    let overlap_x = a.x1.max(b.x1) < a.x2.min(b.x2);
+    let overlap_y = a.y1.max(b.y1) < a.y2.min(b.y2);
+    (overlap_y && (a.x2 == b.x1 || b.x2 == a.x1)) ||
+    (overlap_x && (a.y2 == b.y1 || b.y2 == a.y1))
+}
+
+// Region growing over the partition: adjacent regions are merged,
+// most similar first, while their optimal colors differ by less than the threshold.
+fn merge_similar(img: &Image, leaves: &[Shape], threshold: f64) -> Vec<Region> {
+    let _t = crate::stats_timer!("quilt/merge_similar").time_it();
+    let mut regions: Vec<Option<Region>> = leaves.iter().map(|&shape| {
+        let cf = color_freqs(img, &shape);
+        let color = optimal_color_for_color_freqs(&cf);
+        Some(Region { shapes: vec![shape], color_freqs: cf, color })
+    }).collect();
+
+    let mut owner: Vec<usize> = (0..leaves.len()).collect();
+    let mut pairs = vec![];
+    for i in 0..leaves.len() {
+        for j in 0..i {
+            if adjacent(leaves[i], leaves[j]) {
+                pairs.push((i, j));
+            }
+        }
+    }
+
+    loop {
+        let best = pairs.iter()
+            .map(|&(i, j)| (owner[i], owner[j]))
+            .filter(|(ri, rj)| ri != rj)
+            .map(|(ri, rj)| {
+                let d = regions[ri].as_ref().unwrap().color.dist(&regions[rj].as_ref().unwrap().color);
+                (d, ri, rj)
+            })
+            .filter(|&(d, _, _)| d < threshold)
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let Some((_, ri, rj)) = best else { break };
+
+        let absorbed = regions[rj].take().unwrap();
+        let region = regions[ri].as_mut().unwrap();
+        for (k, v) in absorbed.color_freqs {
+            *region.color_freqs.entry(k).or_default() += v;
+        }
+        region.color = optimal_color_for_color_freqs(&region.color_freqs);
+        region.shapes.extend(absorbed.shapes);
+        for o in &mut owner {
+            if *o == rj {
+                *o = ri;
+            }
+        }
+    }
+
+    let mut regions: Vec<Region> = regions.into_iter().flatten().collect();
+    for region in &mut regions {
+        combine_shapes(&mut region.shapes);
+    }
+    regions
+}
+
+// Fewer and larger rectangles are cheaper to isolate.
+fn combine_shapes(shapes: &mut Vec<Shape>) {
+    'outer: loop {
+        for i in 0..shapes.len() {
+            for j in 0..i {
+                if let Some(merged) = merge_shapes(shapes[i], shapes[j]) {
+                    shapes.swap_remove(i);
+                    shapes[j] = merged;
+                    continue 'outer;
+                }
+            }
+        }
+        return;
+    }
+}
+
+fn paint(problem: &Problem, mut regions: Vec<Region>) -> Vec<Move> {
+    let _t = crate::stats_timer!("quilt/paint").time_it();
+    regions.sort_by_key(|r| -r.size());
+
+    let mut painter = PainterState::new(problem);
+    let (mut root, _) = seg_util::merge_all(&mut painter);
+    let mut best_score = painter.cost + image_distance(&problem.target, &painter.render()).round() as i64;
+
+    // The largest region becomes the background, so its rectangles come for free.
+    let mut jobs: Vec<(Option<Shape>, Color)> = vec![(None, regions[0].color)];
+    for region in &regions[1..] {
+        for &shape in &region.shapes {
+            jobs.push((Some(shape), region.color));
+        }
+    }
+    jobs[1..].sort_by_key(|(shape, _)| -shape.unwrap().size());
+
+    for (shape, color) in jobs {
+        let checkpoint = painter.moves.len();
+        let mut new_root = root.clone();
+        if let Some(shape) = shape {
+            let (id, _) = seg_util::isolate_rect(&mut painter, root.clone(), shape);
+            painter.apply_move(&ColorMove { block_id: id, color });
+            new_root = seg_util::merge_all(&mut painter).0;
+        } else {
+            painter.apply_move(&ColorMove { block_id: root.clone(), color });
+        }
+        let score = painter.cost + image_distance(&problem.target, &painter.render()).round() as i64;
+        if score < best_score {
+            best_score = score;
+            root = new_root;
+        } else {
+            while painter.moves.len() > checkpoint {
+                painter.rollback_move();
+            }
+        }
+    }
+    painter.moves
+}
+
+#[test]
+fn test_solve_replays() {
+    let problem = Problem::load(1);
+    let (score, moves) = solve(&SolverArgs { threshold: 20.0, granularity: 40 }, &problem);
+    let mut painter = PainterState::new(&problem);
+    for m in &moves {
+        painter.try_apply_move(m).unwrap_or_else(|e| panic!("{}: {}", m, e));
+    }
+    assert_eq!(score, painter.cost + image_distance(&problem.target, &painter.render()).round() as i64);
+}
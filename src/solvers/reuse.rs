@@ -0,0 +1,318 @@
+use fxhash::FxHashMap as HashMap;
+use crate::basic::*;
+use crate::basic::Move::*;
+use crate::image::Image;
+use crate::invocation::InvocationGuard;
+use crate::uploader::upload_solution;
+use crate::color_util::{color_freqs, dist_to_color_freqs, optimal_color_for_color_freqs};
+
+// For problems that start with the initial bitmap (36..40).
+// Cuts the canvas along a guillotine partition, where every leaf either keeps
+// the bitmap pixels or is repainted with a flat color, whichever is cheaper.
+// Then leaves of the same size swap their contents if the bitmap of one
+// fits the other place better.
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug)]
+struct SolverArgs {
+    granularity: i32,
+}
+crate::solver_args!(SolverArgs, "reuse", 1);
+
+crate::entry_point!("reuse_solver", reuse_solver);
+fn reuse_solver() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: String = pargs.value_from_str("--problem").unwrap();
+    let granularity: i32 = pargs.value_from_str("--granularity").unwrap_or(20);
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
+
+    let problem_range = crate::util::parse_range(&problems);
+    let args = SolverArgs { granularity };
+
+    let mut store = crate::store::open();
+    for problem_id in problem_range {
+        eprintln!("*********** problem {} ***********", problem_id);
+        let problem = Problem::load(problem_id);
+        if problem.initial_img.is_none() {
+            eprintln!("no initial bitmap, skipping");
+            continue;
+        }
+
+        let moves = solve(&args, &problem);
+
+        let mut tx = store.transaction();
+        upload_solution(&mut *tx, problem_id, &moves, "reuse", &serde_json::to_value(&args).unwrap(), invocation.id(), None);
+        if dry_run {
+            eprintln!("But not really, because it was a --dry-run!");
+        } else {
+            tx.commit();
+        }
+    }
+}
+
+fn solve(args: &SolverArgs, problem: &Problem) -> Vec<Move> {
+    let _t = crate::stats_timer!("reuse/solve").time_it();
+    let initial = problem.initial_img.as_ref().unwrap();
+    let mismatch = Mismatch::new(initial, &problem.target);
+
+    let mut painter = PainterState::new(problem);
+    let (root, _) = crate::seg_util::merge_all(&mut painter);
+
+    let (estimate, tree) = partition(problem, &mismatch, painter.blocks[&root].shape, args.granularity);
+    eprintln!("  estimated score {:.0}", estimate + painter.cost as f64);
+
+    let mut leaves = vec![];
+    apply_cuts(&mut painter, root, &tree, &mut leaves);
+    eprintln!("  {} leaves", leaves.len());
+
+    let contents = plan_swaps(problem, initial, &leaves);
+    let num_moves = painter.moves.len();
+    let ids = apply_swaps(&mut painter, &leaves, &contents);
+    let num_swaps = painter.moves.len() - num_moves;
+
+    let mut num_recolored = 0;
+    for (i, leaf) in leaves.iter().enumerate() {
+        let bitmap = bitmap_dist(initial, &problem.target, leaves[contents[i]].shape, leaf.shape);
+        if leaf.flat_score < bitmap {
+            painter.apply_move(&ColorMove { block_id: ids[i].clone(), color: leaf.color });
+            num_recolored += 1;
+        }
+    }
+    let total_score = painter.cost + image_distance(&problem.target, &painter.render()).round() as i64;
+    eprintln!("  {} swaps, {} recolored, {} kept: {}",
+        num_swaps, num_recolored, leaves.len() - num_recolored, total_score);
+    painter.moves
+}
+
+// Prefix sums of pixel distances between the initial bitmap and the target,
+// so that the cost of keeping any rectangle in place is O(1).
+struct Mismatch {
+    width: i32,
+    sums: Vec<f64>,
+}
+
+impl Mismatch {
+    fn new(initial: &Image, target: &Image) -> Mismatch {
+        let (w, h) = (target.width, target.height);
+        let mut sums = vec![0.0; ((w + 1) * (h + 1)) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                let d = initial.get_pixel(x, y).dist(&target.get_pixel(x, y));
+                let i = ((y + 1) * (w + 1) + x + 1) as usize;
+                sums[i] = d + sums[i - 1] + sums[i - (w + 1) as usize] - sums[i - (w + 2) as usize];
+            }
+        }
+        Mismatch { width: w, sums }
+    }
+
+    fn dist(&self, shape: Shape) -> f64 {
+        let at = |x: i32, y: i32| self.sums[(y * (self.width + 1) + x) as usize];
+        let s = at(shape.x2, shape.y2) - at(shape.x1, shape.y2) - at(shape.x2, shape.y1) + at(shape.x1, shape.y1);
+        s * 0.005
+    }
+}
+
+fn bitmap_dist(initial: &Image, target: &Image, from: Shape, to: Shape) -> f64 {
+    image_slices_distance(initial, target, from, to)
+}
+
+enum Node {
+    Leaf {
+        shape: Shape,
+        color: Color,
+        flat_score: f64,
+    },
+    Split {
+        orientation: Orientation,
+        line_number: i32,
+        children: Box<[Node; 2]>,
+    },
+}
+
+// Score of a region that is left alone, and of a region that gets a ColorMove.
+fn leaf(problem: &Problem, mismatch: &Mismatch, shape: Shape) -> (f64, Node) {
+    let cf = color_freqs(&problem.target, &shape);
+    let color = optimal_color_for_color_freqs(&cf);
+    let flat_score = dist_to_color_freqs(&cf, color) + problem.cost(problem.base_costs.color, shape.size()) as f64;
+    let keep_score = mismatch.dist(shape);
+    (keep_score.min(flat_score), Node::Leaf { shape, color, flat_score })
+}
+
+// Same scheme as in the quilt solver: the tree is grown down to the granularity,
+// choosing cuts by one step lookahead, and pruned bottom-up.
+fn partition(problem: &Problem, mismatch: &Mismatch, shape: Shape, granularity: i32) -> (f64, Node) {
+    let (leaf_score, leaf_node) = leaf(problem, mismatch, shape);
+    let lcut_cost = problem.cost(problem.base_costs.lcut, shape.size()) as f64;
+    if leaf_score <= lcut_cost {
+        return (leaf_score, leaf_node);
+    }
+
+    let mut best: Option<(f64, Orientation, i32)> = None;
+    for orientation in [Orientation::Vertical, Orientation::Horizontal] {
+        let (lo, hi) = match orientation {
+            Orientation::Vertical => (shape.x1, shape.x2),
+            Orientation::Horizontal => (shape.y1, shape.y2),
+        };
+        for line_number in (lo + 1..hi).filter(|line| line % granularity == 0) {
+            let score: f64 = shape.l_cut_subshapes(orientation, line_number).iter()
+                .map(|&half| leaf(problem, mismatch, half).0)
+                .sum();
+            if best.map(|b| score < b.0).unwrap_or(true) {
+                best = Some((score, orientation, line_number));
+            }
+        }
+    }
+    let Some((_, orientation, line_number)) = best else {
+        return (leaf_score, leaf_node);
+    };
+
+    let [h0, h1] = shape.l_cut_subshapes(orientation, line_number);
+    let (s0, n0) = partition(problem, mismatch, h0, granularity);
+    let (s1, n1) = partition(problem, mismatch, h1, granularity);
+    let split_score = lcut_cost + s0 + s1;
+    if split_score < leaf_score {
+        (split_score, Node::Split { orientation, line_number, children: Box::new([n0, n1]) })
+    } else {
+        (leaf_score, leaf_node)
+    }
+}
+
+struct Leaf {
+    block_id: BlockId,
+    shape: Shape,
+    color: Color,
+    flat_score: f64,
+}
+
+fn apply_cuts(painter: &mut PainterState, block_id: BlockId, node: &Node, leaves: &mut Vec<Leaf>) {
+    match node {
+        Node::Leaf { shape, color, flat_score } => {
+            assert_eq!(painter.blocks[&block_id].shape, *shape);
+            leaves.push(Leaf { block_id, shape: *shape, color: *color, flat_score: *flat_score });
+        }
+        Node::Split { orientation, line_number, children } => {
+            let ids = painter.apply_move(&LCut {
+                block_id,
+                orientation: *orientation,
+                line_number: *line_number,
+            }).new_block_ids;
+            for (id, child) in ids.into_iter().zip(children.iter()) {
+                apply_cuts(painter, id, child, leaves);
+            }
+        }
+    }
+}
+
+// Moves the initial pixels of leaf contents[i] to the place of leaf i.
+// Returns the ID of the block at each place afterwards.
+fn apply_swaps(painter: &mut PainterState, leaves: &[Leaf], contents: &[usize]) -> Vec<BlockId> {
+    // Swap moves the block ID along with its contents,
+    // so keep track of which block is at each place.
+    let mut ids: Vec<BlockId> = leaves.iter().map(|l| l.block_id.clone()).collect();
+    let mut place: Vec<usize> = (0..leaves.len()).collect();
+    for target in 0..leaves.len() {
+        let src = contents[target];
+        let cur = place[src];
+        if cur == target {
+            continue;
+        }
+        painter.apply_move(&Swap { block_id1: ids[cur].clone(), block_id2: ids[target].clone() });
+        let displaced = place.iter().position(|&p| p == target).unwrap();
+        place[displaced] = cur;
+        place[src] = target;
+        ids.swap(cur, target);
+    }
+    ids
+}
+
+// Returns contents[i] = index of the leaf whose initial pixels should end up in place of leaf i.
+// Greedy local search over pairwise swaps between leaves of the same size.
+fn plan_swaps(problem: &Problem, initial: &Image, leaves: &[Leaf]) -> Vec<usize> {
+    let _t = crate::stats_timer!("reuse/plan_swaps").time_it();
+    let mut contents: Vec<usize> = (0..leaves.len()).collect();
+
+    let mut groups: HashMap<(i32, i32), Vec<usize>> = HashMap::default();
+    for (i, leaf) in leaves.iter().enumerate() {
+        groups.entry((leaf.shape.width(), leaf.shape.height())).or_default().push(i);
+    }
+
+    let mut cache: HashMap<(usize, usize), f64> = HashMap::default();
+    let mut value = |src: usize, dst: usize| -> f64 {
+        let bitmap = *cache.entry((src, dst)).or_insert_with(||
+            bitmap_dist(initial, &problem.target, leaves[src].shape, leaves[dst].shape));
+        bitmap.min(leaves[dst].flat_score)
+    };
+
+    for group in groups.values().filter(|g| g.len() > 1) {
+        let swap_cost = problem.cost(problem.base_costs.swap, leaves[group[0]].shape.size()) as f64;
+        loop {
+            let mut best: Option<(f64, usize, usize)> = None;
+            for (k, &a) in group.iter().enumerate() {
+                for &b in &group[..k] {
+                    let current = value(contents[a], a) + value(contents[b], b);
+                    let swapped = value(contents[b], a) + value(contents[a], b);
+                    let gain = current - swapped - swap_cost;
+                    if gain > 0.0 && best.map(|(g, _, _)| gain > g).unwrap_or(true) {
+                        best = Some((gain, a, b));
+                    }
+                }
+            }
+            let Some((_, a, b)) = best else { break };
+            contents.swap(a, b);
+        }
+    }
+    contents
+}
+
+#[test]
+fn test_solve_replays() {
+    let problem = Problem::load(36);
+    let moves = solve(&SolverArgs { granularity: 40 }, &problem);
+    let mut painter = PainterState::new(&problem);
+    for m in &moves {
+        painter.try_apply_move(m).unwrap_or_else(|e| panic!("{}: {}", m, e));
+    }
+}
+
+#[test]
+fn test_apply_swaps() {
+    let problem = Problem::load(36);
+    let initial = problem.initial_img.as_ref().unwrap();
+    for contents in [vec![1, 2, 0, 3], vec![3, 2, 1, 0], vec![0, 3, 1, 2]] {
+        let mut painter = PainterState::new(&problem);
+        let (mut rest, _) = crate::seg_util::merge_all(&mut painter);
+        // four 100px wide columns
+        let mut leaves = vec![];
+        for x in [100, 200, 300] {
+            let ids = painter.apply_move(&LCut { block_id: rest, orientation: Orientation::Vertical, line_number: x }).new_block_ids;
+            let [left, right] = <[BlockId; 2]>::try_from(ids).unwrap();
+            leaves.push(left);
+            rest = right;
+        }
+        leaves.push(rest);
+        let leaves: Vec<Leaf> = leaves.into_iter().map(|block_id| Leaf {
+            shape: painter.blocks[&block_id].shape,
+            block_id,
+            color: Color::default(),
+            flat_score: 0.0,
+        }).collect();
+        leaves.windows(2).for_each(|w| assert!(w[0].shape.x1 < w[1].shape.x1));
+
+        let ids = apply_swaps(&mut painter, &leaves, &contents);
+        let img = painter.render();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(painter.blocks[&ids[i]].shape, leaf.shape);
+            let src = leaves[contents[i]].shape;
+            for (x, y) in [(0, 0), (37, 150), (99, 399)] {
+                assert_eq!(img.get_pixel(leaf.shape.x1 + x, y), initial.get_pixel(src.x1 + x, y), "{:?} place {}", contents, i);
+            }
+        }
+        let mut replay = PainterState::new(&problem);
+        for m in &painter.moves {
+            replay.try_apply_move(m).unwrap_or_else(|e| panic!("{}: {}", m, e));
+        }
+    }
+}
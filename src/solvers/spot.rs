@@ -4,7 +4,7 @@ use fxhash::FxHashMap as HashMap;
 use rand::prelude::*;
 use crate::basic::*;
 use crate::basic::Move::*;
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 use crate::color_util::optimal_color_for_color_freqs;
 use crate::seg_util;
@@ -12,6 +12,7 @@ use crate::util::project_path;
 
 crate::entry_point!("spot_solver", spot_solver);
 fn spot_solver() {
+    let invocation = InvocationGuard::start();
     let problem_id = 40;
     let mut best_score = 1_000_000_000;
     let mut best_moves = vec![];
@@ -106,9 +107,8 @@ fn spot_solver() {
     }
     painter.render().save(&project_path("outputs/spot.png"));
 
-    let mut client = crate::db::create_client();
-    let mut tx = client.transaction().unwrap();
-    let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-    upload_solution(&mut tx, problem_id, &best_moves, "spot", &serde_json::Value::Null, incovation_id);
-    tx.commit().unwrap();
+    let mut store = crate::store::open();
+    let mut tx = store.transaction();
+    upload_solution(&mut *tx, problem_id, &best_moves, "spot", &serde_json::Value::Null, invocation.id(), None);
+    tx.commit();
 }
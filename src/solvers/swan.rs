@@ -3,20 +3,21 @@
 use crate::util::project_path;
 use crate::basic::*;
 use crate::image::Image;
-use crate::invocation::{record_this_invocation, Status};
+use crate::invocation::InvocationGuard;
 use crate::uploader::upload_solution;
 use crate::color_util::*;
 use crate::seg_util;
 
 use crate::basic::Move::*;
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Debug)]
 struct SolverArgs {
     px: i32,
     py: i32,
     num_colors: usize,
 }
+crate::solver_args!(SolverArgs, "swan", 1);
 
 crate::entry_point!("swan_solver", swan_solver);
 fn swan_solver() {
@@ -25,6 +26,7 @@ fn swan_solver() {
     let dry_run = pargs.contains("--dry-run");
     let rest = pargs.finish();
     assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
 
     let problem_range = match problems.split_once("..") {
         Some((left, right)) => {
@@ -60,14 +62,13 @@ fn swan_solver() {
 
         eprintln!("BEST:  {:?}: {}", args, total_score);
 
-        let mut client = crate::db::create_client();
-        let mut tx = client.transaction().unwrap();
-        let incovation_id = record_this_invocation(&mut tx, Status::Stopped);
-        upload_solution(&mut tx, problem_id, &moves, "swan", &serde_json::to_value(args).unwrap(), incovation_id);
+        let mut store = crate::store::open();
+        let mut tx = store.transaction();
+        upload_solution(&mut *tx, problem_id, &moves, "swan", &serde_json::to_value(args).unwrap(), invocation.id(), None);
         if dry_run {
             eprintln!("But not really, because it was a --dry-run!");
         } else {
-            tx.commit().unwrap();
+            tx.commit();
         }
         eprintln!("{}", crate::stats::STATS.render());
     }
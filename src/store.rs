@@ -0,0 +1,347 @@
+// Everything that our tools save or look up goes through the Store trait,
+// so that they can work either with the shared Postgres DB (see db.rs)
+// or offline, with JSON lines files under cache/ (see local_store.rs).
+//
+// The backend is selected with TBD_STORE env var:
+//   TBD_STORE=postgres  (default)
+//   TBD_STORE=local
+// TBD_EXCLUDE_INVALID=1 leaves solutions that failed verification out of leaderboards.
+
+use std::fmt::Write;
+use sha2::Digest;
+
+use crate::basic::Move;
+use crate::invocation::Invocation;
+use crate::score_estimate::ScoreEstimate;
+use crate::util::DateTime;
+
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SolutionRow {
+    pub id: i32,
+    pub problem_id: i32,
+    pub moves_cost: i64,
+    pub image_distance: i64,
+    pub score: i64,
+    pub solver_name: String,
+    pub solver_args: serde_json::Value,
+    #[serde(default)]
+    pub solver_args_version: Option<i32>,  // see solver_args.rs
+    pub invocation_id: i32,
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub derivation: Option<String>,
+}
+
+pub struct NewSolution<'a> {
+    pub problem_id: i32,
+    pub data: &'a str,
+    pub content_hash: &'a str,
+    pub moves_cost: i64,
+    pub image_distance: i64,
+    pub solver_name: &'a str,
+    pub solver_args: &'a serde_json::Value,
+    pub solver_args_version: Option<i32>,
+    pub invocation_id: i32,
+    pub derivation: Option<Derivation<'a>>,
+}
+
+// For solutions produced by post-processing another solution
+// (recoloring, restarting a search from it, etc).
+#[derive(Clone, Copy, Debug)]
+pub struct Derivation<'a> {
+    pub parent_id: i32,
+    pub label: &'a str,  // what was done to the parent, like "recolor"
+}
+
+pub enum InsertedSolution {
+    New(i32),
+    // Identical moves for the same problem were uploaded before, nothing is inserted.
+    AlreadyKnown(i32),
+}
+
+pub struct InvocationRow {
+    pub id: i32,
+    pub status: String,  // "RUN", "STOPPED", "FAILED" or "LOST", see migrations/001_initial.sql
+    pub start_time: DateTime,
+    pub update_time: DateTime,
+    pub data: Invocation,
+}
+
+// Result of replaying a stored solution with the current PainterState,
+// see verify_solutions entry point.
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Verification {
+    pub solution_id: i32,
+    // None if the solution can't be replayed.
+    pub moves_cost: Option<i64>,
+    pub image_distance: Option<i64>,
+    pub error: Option<String>,  // None if the solution is valid
+    pub invocation_id: i32,
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+}
+
+impl Verification {
+    pub fn valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    // Valid, but the score is not what was recorded on upload.
+    pub fn score_changed(&self, s: &SolutionRow) -> bool {
+        self.valid() && (self.moves_cost != Some(s.moves_cost) || self.image_distance != Some(s.image_distance))
+    }
+}
+
+// Successful submission where the server's score is not what we computed,
+// see reconcile_scores entry point.
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ScoreMismatch {
+    pub submission_id: i32,
+    pub solution_id: i32,
+    pub our_score: i64,
+    pub their_score: i64,
+    // First move where a plausible alternative reading of the cost rules gives a different cost.
+    pub suspect_move: Option<i32>,
+    pub explanation: String,
+    pub invocation_id: i32,
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Parse,
+    InvalidBlockId,
+    Geometry,
+    // Valid as far as we can tell.
+    ServerSide,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FailureKind::Parse => "parse",
+            FailureKind::InvalidBlockId => "invalid_block_id",
+            FailureKind::Geometry => "geometry",
+            FailureKind::ServerSide => "server_side",
+        }
+    }
+
+    pub fn parse(s: &str) -> FailureKind {
+        match s {
+            "parse" => FailureKind::Parse,
+            "invalid_block_id" => FailureKind::InvalidBlockId,
+            "geometry" => FailureKind::Geometry,
+            "server_side" => FailureKind::ServerSide,
+            _ => panic!("unknown failure kind {:?}", s),
+        }
+    }
+}
+
+// Why a submission failed, see triage_submissions entry point.
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Triage {
+    pub submission_id: i32,
+    pub kind: FailureKind,
+    pub move_index: Option<i32>,
+    pub details: String,
+    // The invocation that produced the failing solution (not the triage run), if known.
+    pub invocation_id: Option<i32>,
+    #[serde(with = "datetime_format")]
+    pub timestamp: DateTime,
+}
+
+#[derive(Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum SubmissionResult {
+    Pending,
+    Good { cost: i64, file_url: String },
+    Bad { error: String, file_url: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct SubmissionRow {
+    pub submission_id: i32,
+    pub problem_id: i32,
+    pub solution_id: i32,
+    pub timestamp: DateTime,
+    pub result: SubmissionResult,
+}
+
+pub trait Store {
+    fn transaction(&mut self) -> Box<dyn StoreTransaction + '_>;
+
+    fn find_solution(&mut self, id: i32) -> Option<SolutionRow>;
+    fn solution(&mut self, id: i32) -> SolutionRow {
+        self.find_solution(id).unwrap_or_else(|| panic!("no solution {}", id))
+    }
+    // Move instructions as text.
+    fn solution_data(&mut self, id: i32) -> String;
+    // All solutions (for all problems if problem_id is None), ordered by timestamp.
+    // Old duplicates (see backfill_content_hashes) are left out of this and of the leaderboards.
+    fn solutions(&mut self, problem_id: Option<i32>) -> Vec<SolutionRow>;
+    // Earliest of the solutions with the lowest score.
+    fn best_solution(&mut self, problem_id: i32) -> Option<SolutionRow>;
+
+    // Leaderboards. Ties are broken by the earliest timestamp,
+    // ordered by problem_id, and then from best to worst.
+    // Solutions that failed verification are left out if exclude_invalid is set
+    // (it also applies to best_solution()).
+
+    fn exclude_invalid(&self) -> bool;
+    fn set_exclude_invalid(&mut self, exclude: bool);
+
+    // Solutions that leaderboards are made of.
+    fn leaderboard_candidates(&mut self, problem_id: Option<i32>) -> Vec<SolutionRow> {
+        let mut rows = self.solutions(problem_id);
+        if self.exclude_invalid() {
+            let invalid: fxhash::FxHashSet<i32> = self.verifications().into_iter()
+                .filter(|v| !v.valid())
+                .map(|v| v.solution_id)
+                .collect();
+            rows.retain(|s| !invalid.contains(&s.id));
+        }
+        rows
+    }
+    // Best solution for every problem that has any, see also leaderboard().
+    fn best_solutions(&mut self) -> Vec<SolutionRow> {
+        self.top_solutions(1)
+    }
+    // Up to n best solutions for every problem.
+    fn top_solutions(&mut self, n: usize) -> Vec<SolutionRow> {
+        let mut rows = self.leaderboard_candidates(None);
+        rows.sort_by_key(|s| (s.problem_id, s.score, s.timestamp));
+        let mut result: Vec<SolutionRow> = vec![];
+        for s in rows {
+            if result.iter().rev().take_while(|r| r.problem_id == s.problem_id).count() < n {
+                result.push(s);
+            }
+        }
+        result
+    }
+    // Best solution of every solver for every problem.
+    fn solver_bests(&mut self) -> Vec<SolutionRow> {
+        let mut rows = self.leaderboard_candidates(None);
+        rows.sort_by(|a, b| (a.problem_id, &a.solver_name, a.score, a.timestamp)
+            .cmp(&(b.problem_id, &b.solver_name, b.score, b.timestamp)));
+        rows.dedup_by(|b, a| (a.problem_id, &a.solver_name) == (b.problem_id, &b.solver_name));
+        rows.sort_by_key(|s| (s.problem_id, s.score, s.timestamp));
+        rows
+    }
+
+    fn find_invocation(&mut self, id: i32) -> Option<InvocationRow>;
+    fn invocation(&mut self, id: i32) -> InvocationRow {
+        self.find_invocation(id).unwrap_or_else(|| panic!("no invocation {}", id))
+    }
+    fn invocations(&mut self) -> Vec<InvocationRow>;
+
+    // Ordered by timestamp.
+    fn submissions(&mut self) -> Vec<SubmissionRow>;
+
+    fn score_estimates(&mut self) -> Vec<(i32, ScoreEstimate)>;
+
+    // The latest verification of every verified solution.
+    fn verifications(&mut self) -> Vec<Verification>;
+
+    // The latest analysis of every mismatching submission.
+    fn score_mismatches(&mut self) -> Vec<ScoreMismatch>;
+
+    // The latest triage of every failed submission that was triaged.
+    fn triages(&mut self) -> Vec<Triage>;
+}
+
+// Nothing is written until commit(). Dropping it without commit discards the changes.
+pub trait StoreTransaction {
+    // Inserts invocation if id is None.
+    // delta_time is the time to the next planned update, see invocation::record_this_invocation().
+    fn record_invocation(&mut self, id: Option<i32>, status: &str, delta_time: f64, data: &Invocation) -> i32;
+    fn insert_solution(&mut self, s: &NewSolution) -> InsertedSolution;
+    fn insert_submission(&mut self, problem_id: i32, solution_id: i32, submission_id: i32);
+    // Does nothing if the result is already recorded.
+    fn record_submission_result(&mut self, submission_id: i32, result: &SubmissionResult);
+    fn set_score_estimate(&mut self, problem_id: i32, est: &ScoreEstimate);
+    fn record_verification(&mut self, v: &Verification);
+    fn record_score_mismatch(&mut self, m: &ScoreMismatch);
+    fn record_triage(&mut self, t: &Triage);
+    fn commit(self: Box<Self>);
+}
+
+// One move per line, as Move's Display writes it.
+// Solutions that differ only in formatting are the same.
+pub fn canonical_text(moves: &[Move]) -> String {
+    let mut text = String::new();
+    for m in moves {
+        writeln!(text, "{}", m).unwrap();
+    }
+    text
+}
+
+// SHA-256 of canonical_text(), hex.
+pub fn content_hash(moves: &[Move]) -> String {
+    let digest = sha2::Sha256::digest(canonical_text(moves).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Best solution for each of the problems (all of them by default), None if it has no solutions.
+pub fn leaderboard(store: &mut dyn Store, problem_ids: Option<&[i32]>) -> Vec<(i32, Option<SolutionRow>)> {
+    let all_ids;
+    let problem_ids = match problem_ids {
+        Some(ids) => ids,
+        None => {
+            all_ids = crate::basic::Problem::all_ids();
+            &all_ids
+        }
+    };
+    let mut best: fxhash::FxHashMap<i32, SolutionRow> = store.best_solutions().into_iter()
+        .map(|s| (s.problem_id, s))
+        .collect();
+    problem_ids.iter().map(|&id| (id, best.remove(&id))).collect()
+}
+
+pub fn open() -> Box<dyn Store + Send> {
+    let kind = std::env::var("TBD_STORE").unwrap_or_else(|_| "postgres".to_owned());
+    let mut store: Box<dyn Store + Send> = match kind.as_str() {
+        "postgres" => Box::new(crate::db::PgStore::new(crate::db::POOL.get())),
+        "local" => Box::new(crate::local_store::LocalStore::new(crate::util::project_path("cache/store"))),
+        _ => panic!("unknown TBD_STORE={:?}, expected 'postgres' or 'local'", kind),
+    };
+    if std::env::var("TBD_EXCLUDE_INVALID").is_ok_and(|v| v == "1") {
+        store.set_exclude_invalid(true);
+    }
+    store
+}
+
+// chrono is built without serde support.
+pub mod datetime_format {
+    use crate::util::DateTime;
+
+    pub fn serialize<S: serde::Serializer>(t: &DateTime, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&t.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<DateTime, D::Error> {
+        let s: String = serde::Deserialize::deserialize(d)?;
+        chrono::DateTime::parse_from_rfc3339(&s)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn test_content_hash() {
+    let a = Move::parse_many("color [0] [1, 2, 3, 4]\ncut [0] [x] [10]\n");
+    let b = Move::parse_many("  color [0] [1,2,3,4]\n\ncut [0]  [x][10]  ");
+    let c = Move::parse_many("cut [0] [x] [10]\ncolor [0] [1, 2, 3, 4]\n");
+    assert_eq!(content_hash(&a), content_hash(&b));
+    assert_ne!(content_hash(&a), content_hash(&c));
+    assert_eq!(content_hash(&[]).len(), 64);
+}
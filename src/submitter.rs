@@ -0,0 +1,180 @@
+// Long-running job that keeps the contest server up to date with our best solutions.
+// Each round it polls pending submissions and submits problems where we've improved
+// over what's already submitted. Everything goes through api.rs, so it's recorded
+// in the store as usual.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use fxhash::FxHashMap as HashMap;
+use fxhash::FxHashSet as HashSet;
+
+use crate::api::{check_submission, submit_solution, ApiError, Conf};
+use crate::invocation::InvocationGuard;
+use crate::store::{SolutionRow, Store, SubmissionResult};
+
+// Best solutions that are better than anything submitted for their problem.
+// Pending submissions count with our own score, so that we don't submit twice in a row
+// while the server is processing. Solutions by blocked solvers are not considered.
+pub fn improvements(store: &mut dyn Store, problem_ids: Option<&[i32]>, blocked: &HashSet<String>) -> Vec<SolutionRow> {
+    let mut submitted_ids: HashSet<i32> = HashSet::default();
+    let mut submitted_scores: HashMap<i32, i64> = HashMap::default();
+    for sub in store.submissions() {
+        submitted_ids.insert(sub.solution_id);
+        let score = match sub.result {
+            SubmissionResult::Good { cost, .. } => cost,
+            SubmissionResult::Pending => store.solution(sub.solution_id).score,
+            SubmissionResult::Bad { .. } => continue,
+        };
+        let e = submitted_scores.entry(sub.problem_id).or_insert(score);
+        *e = (*e).min(score);
+    }
+    // solver_bests() is ordered from best to worst within each problem.
+    let mut bests: BTreeMap<i32, SolutionRow> = BTreeMap::new();
+    for s in store.solver_bests() {
+        if blocked.contains(&s.solver_name) || problem_ids.is_some_and(|ids| !ids.contains(&s.problem_id)) {
+            continue;
+        }
+        bests.entry(s.problem_id).or_insert(s);
+    }
+    bests.into_values()
+        .filter(|best| !submitted_ids.contains(&best.id))
+        .filter(|best| submitted_scores.get(&best.problem_id).is_none_or(|&s| best.score < s))
+        .collect()
+}
+
+pub struct Submitter {
+    conf: Conf,
+    problem_ids: Option<Vec<i32>>,
+    // Between consecutive submissions.
+    min_interval: Duration,
+    last_submit: Option<Instant>,
+    // Solutions the server refused to take, they are not retried.
+    refused: HashSet<i32>,
+    // Submitted even if blocked, see triage.rs.
+    allowed_solvers: HashSet<String>,
+}
+
+impl Submitter {
+    pub fn new(conf: Conf, problem_ids: Option<Vec<i32>>, min_interval: Duration, allowed_solvers: HashSet<String>) -> Submitter {
+        Submitter { conf, problem_ids, min_interval, last_submit: None, refused: HashSet::default(), allowed_solvers }
+    }
+
+    // Polls pending submissions, then submits improvements.
+    // Stops at the first transient error, the rest is retried next round.
+    pub fn round(&mut self, store: &mut dyn Store) -> Result<(), ApiError> {
+        for sub in store.submissions() {
+            if !matches!(sub.result, SubmissionResult::Pending) {
+                continue;
+            }
+            match check_submission(&self.conf, store, sub.submission_id) {
+                Ok(status) => eprintln!("submission {} (problem {}): {}", sub.submission_id, sub.problem_id, status.status),
+                Err(e) if e.is_transient() => return Err(e),
+                Err(e) => eprintln!("can't check submission {}: {}", sub.submission_id, e),
+            }
+        }
+
+        let mut blocked = crate::triage::blocked_solvers(store);
+        blocked.retain(|s| !self.allowed_solvers.contains(s));
+        if !blocked.is_empty() {
+            eprintln!("not submitting solutions by {:?}, see triage_submissions", blocked);
+        }
+        for best in improvements(store, self.problem_ids.as_deref(), &blocked) {
+            if self.refused.contains(&best.id) {
+                continue;
+            }
+            if let Some(last) = self.last_submit {
+                std::thread::sleep(self.min_interval.saturating_sub(last.elapsed()));
+            }
+            self.last_submit = Some(Instant::now());
+            match submit_solution(&self.conf, store, best.id) {
+                Ok(submission_id) => eprintln!("submitted solution/{} for problem {}, score {} (submission {})",
+                    best.id, best.problem_id, best.score, submission_id),
+                Err(e) if e.is_transient() => return Err(e),
+                Err(e) => {
+                    eprintln!("server refused solution/{} for problem {}: {}", best.id, best.problem_id, e);
+                    self.refused.insert(best.id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+crate::entry_point!("submitter", submitter);
+fn submitter() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: Option<String> = pargs.opt_value_from_str("--problem").unwrap();
+    let min_interval: f64 = pargs.opt_value_from_str("--min-interval").unwrap().unwrap_or(30.0);
+    let poll_interval: f64 = pargs.opt_value_from_str("--poll-interval").unwrap().unwrap_or(60.0);
+    let once = pargs.contains("--once");
+    let mut allowed_solvers: HashSet<String> = HashSet::default();
+    while let Some(solver) = pargs.opt_value_from_str("--allow-solver").unwrap() {
+        allowed_solvers.insert(solver);
+    }
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let _invocation = InvocationGuard::start();
+
+    let problem_ids = problems.map(|p| crate::util::parse_range(&p).collect());
+    let mut submitter = Submitter::new(Conf::load(), problem_ids, Duration::from_secs_f64(min_interval), allowed_solvers);
+    let poll_interval = Duration::from_secs_f64(poll_interval);
+    let mut backoff = poll_interval;
+    loop {
+        let mut store = crate::store::open();
+        match submitter.round(&mut *store) {
+            Ok(()) => backoff = poll_interval,
+            Err(e) => {
+                eprintln!("{}, retrying in {:?}", e, backoff);
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(poll_interval.max(Duration::from_secs(600)));
+                continue;
+            }
+        }
+        if once {
+            break;
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+#[test]
+fn test_submitter() {
+    use crate::local_store::{insert_test_solution, LocalStore, TestStore};
+    use crate::store::InsertedSolution;
+
+    let mut store = TestStore::new();
+    let mut tx = store.transaction();
+    insert_test_solution(&mut *tx, 1, 50, 1, 0);
+    insert_test_solution(&mut *tx, 2, 50, 1, 0);
+    tx.commit();
+
+    let mock = crate::mock_server::MockServer::start();
+    mock.set_scorer(|_problem_id, _isl| Ok(200));
+    mock.set_pending_polls(1);
+    let mut submitter = Submitter::new(mock.conf.clone(), Some(vec![1, 2]), Duration::ZERO, HashSet::default());
+    let pending = |store: &mut LocalStore| store.submissions().iter()
+        .filter(|s| matches!(s.result, SubmissionResult::Pending)).count();
+
+    mock.fail_next(1);
+    assert!(submitter.round(&mut *store).unwrap_err().is_transient());
+    assert!(store.submissions().is_empty());
+
+    submitter.round(&mut *store).unwrap();
+    assert_eq!(store.submissions().len(), 2);
+    assert!(improvements(&mut *store, Some(&[1, 2]), &HashSet::default()).is_empty());
+    submitter.round(&mut *store).unwrap();  // still processing
+    assert_eq!(pending(&mut store), 2);
+    submitter.round(&mut *store).unwrap();
+    assert_eq!(pending(&mut store), 0);
+    assert_eq!(mock.submissions().len(), 2);
+
+    let mut tx = store.transaction();
+    let InsertedSolution::New(better) = insert_test_solution(&mut *tx, 1, 20, 2, 0) else { panic!() };
+    tx.commit();
+    assert_eq!(improvements(&mut *store, Some(&[1, 2]), &HashSet::default()).iter().map(|s| s.id).collect::<Vec<_>>(), vec![better]);
+    let blocked: HashSet<String> = ["test".to_owned()].into_iter().collect();
+    assert!(improvements(&mut *store, Some(&[1, 2]), &blocked).is_empty());
+    submitter.round(&mut *store).unwrap();
+    assert_eq!(mock.submissions().len(), 3);
+    assert_eq!(store.submissions().last().unwrap().solution_id, better);
+}
@@ -0,0 +1,116 @@
+// Figures out why submissions were rejected by replaying them locally.
+// Solvers that produced invalid solutions are blocked from auto-submission,
+// see blocked_solvers() and submitter.rs.
+
+use fxhash::FxHashMap as HashMap;
+use fxhash::FxHashSet as HashSet;
+
+use crate::basic::*;
+use crate::invocation::InvocationGuard;
+use crate::store::{FailureKind, Store, SubmissionResult, Triage};
+
+// (kind, index of the offending move, details)
+pub fn classify(problem: &Problem, isl: &str) -> (FailureKind, Option<i32>, String) {
+    let moves = match Move::try_parse_many(isl) {
+        Ok(moves) => moves,
+        Err(e) => return (FailureKind::Parse, None, e),
+    };
+    let mut painter = PainterState::new(problem);
+    for (i, m) in moves.iter().enumerate() {
+        match painter.try_apply_move(m) {
+            Ok(_) => {}
+            Err(e @ MoveError::InvalidBlockId(_)) => return (FailureKind::InvalidBlockId, Some(i as i32), format!("{}: {}", m, e)),
+            Err(e @ MoveError::Geometry(_)) => return (FailureKind::Geometry, Some(i as i32), e.to_string()),
+        }
+    }
+    (FailureKind::ServerSide, None, "valid locally".to_owned())
+}
+
+// Solvers with failed submissions that are their own fault.
+pub fn blocked_solvers(store: &mut dyn Store) -> HashSet<String> {
+    let solution_ids: HashMap<i32, i32> = store.submissions().into_iter()
+        .map(|sub| (sub.submission_id, sub.solution_id))
+        .collect();
+    store.triages().into_iter()
+        .filter(|t| t.kind != FailureKind::ServerSide)
+        .filter_map(|t| solution_ids.get(&t.submission_id))
+        .map(|&solution_id| store.solution(solution_id).solver_name)
+        .collect()
+}
+
+crate::entry_point!("triage_submissions", triage_submissions);
+fn triage_submissions() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let _invocation = InvocationGuard::start();
+
+    let mut store = crate::store::open();
+    let mut triages = vec![];
+    for sub in store.submissions() {
+        let SubmissionResult::Bad { error, .. } = sub.result else { continue };
+        let s = store.solution(sub.solution_id);
+        let problem = Problem::load(sub.problem_id);
+        let (kind, move_index, details) = classify(&problem, &store.solution_data(s.id));
+        eprintln!("submission {} (problem {}, solution/{} by {:?} {}, inv/{}): {}, {}; server said: {}",
+            sub.submission_id, sub.problem_id, s.id, s.solver_name, s.solver_args, s.invocation_id,
+            kind.as_str(), details, error);
+        triages.push(Triage {
+            submission_id: sub.submission_id,
+            kind,
+            move_index,
+            details,
+            invocation_id: Some(s.invocation_id),
+            timestamp: chrono::Utc::now(),
+        });
+    }
+    eprintln!("triaged {} failed submissions", triages.len());
+    if dry_run {
+        eprintln!("But not recording it, because it was a --dry-run!");
+        return;
+    }
+    let mut tx = store.transaction();
+    for t in &triages {
+        tx.record_triage(t);
+    }
+    tx.commit();
+    let blocked = blocked_solvers(&mut *store);
+    if !blocked.is_empty() {
+        eprintln!("blocked from auto-submission: {:?}", blocked);
+    }
+}
+
+#[test]
+fn test_classify() {
+    let problem = Problem::load(1);
+    assert_eq!(classify(&problem, "cut [0] [x] [10]\npaint [0]").0, FailureKind::Parse);
+    assert_eq!(classify(&problem, "cut [0] [x] [10]\ncolor [0] [1, 2, 3, 4]").1, Some(1));
+    assert_eq!(classify(&problem, "cut [0] [x] [10]\ncolor [0] [1, 2, 3, 4]").0, FailureKind::InvalidBlockId);
+    assert_eq!(classify(&problem, "cut [0] [x] [1000]").0, FailureKind::Geometry);
+    assert_eq!(classify(&problem, "cut [0] [x] [10]").0, FailureKind::ServerSide);
+}
+
+#[test]
+fn test_blocked_solvers() {
+    use crate::local_store::{insert_test_solution, TestStore};
+    use crate::store::InsertedSolution;
+
+    let mut store = TestStore::new();
+    let mut tx = store.transaction();
+    let InsertedSolution::New(s1) = insert_test_solution(&mut *tx, 1, 50, 1, 0) else { panic!() };
+    let InsertedSolution::New(s2) = insert_test_solution(&mut *tx, 2, 50, 1, 0) else { panic!() };
+    tx.insert_submission(1, s1, 10);
+    tx.insert_submission(2, s2, 11);
+    let triage = |submission_id, kind| Triage {
+        submission_id, kind, move_index: None, details: String::new(), invocation_id: None, timestamp: chrono::Utc::now(),
+    };
+    tx.record_triage(&triage(10, FailureKind::ServerSide));
+    tx.commit();
+    assert!(blocked_solvers(&mut *store).is_empty());
+
+    let mut tx = store.transaction();
+    tx.record_triage(&triage(11, FailureKind::Geometry));
+    tx.commit();
+    assert_eq!(blocked_solvers(&mut *store).into_iter().collect::<Vec<_>>(), vec!["test".to_owned()]);
+}
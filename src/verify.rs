@@ -0,0 +1,78 @@
+// Replays stored solutions with the current PainterState
+// and records whether they are still valid and score the same.
+// Leaderboards can leave out invalid ones, see Store::set_exclude_invalid().
+
+use crate::basic::*;
+use crate::invocation::{catch_panic, InvocationGuard};
+use crate::store::{SolutionRow, Store, Verification};
+
+pub fn verify_solution(store: &mut dyn Store, s: &SolutionRow, invocation_id: i32) -> Verification {
+    let data = store.solution_data(s.id);
+    let res = catch_panic(|| {
+        let problem = Problem::load(s.problem_id);
+        let moves = Move::parse_many(&data);
+        let mut painter = PainterState::new(&problem);
+        for m in &moves {
+            painter.apply_move(m);
+        }
+        let img = painter.render();
+        (painter.cost, image_distance(&img, &problem.target).round() as i64)
+    });
+    let (moves_cost, image_distance, error) = match res {
+        Ok((cost, dist)) => (Some(cost), Some(dist), None),
+        Err(e) => (None, None, Some(e)),
+    };
+    Verification {
+        solution_id: s.id,
+        moves_cost,
+        image_distance,
+        error,
+        invocation_id,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+crate::entry_point!("verify_solutions", verify_solutions);
+fn verify_solutions() {
+    let mut pargs = pico_args::Arguments::from_vec(std::env::args_os().skip(2).collect());
+    let problems: Option<String> = pargs.opt_value_from_str("--problem").unwrap();
+    let dry_run = pargs.contains("--dry-run");
+    let rest = pargs.finish();
+    assert!(rest.is_empty(), "unrecognized arguments {:?}", rest);
+    let invocation = InvocationGuard::start();
+
+    let mut store = crate::store::open();
+    let problem_ids = match problems {
+        Some(problems) => crate::util::parse_range(&problems).collect(),
+        None => Problem::all_ids(),
+    };
+    let mut verifications = vec![];
+    let mut num_invalid = 0;
+    let mut num_changed = 0;
+    for problem_id in problem_ids {
+        for s in store.solutions(Some(problem_id)) {
+            let v = verify_solution(&mut *store, &s, invocation.id());
+            if let Some(e) = &v.error {
+                num_invalid += 1;
+                eprintln!("solution/{} (problem {}, {:?}) is invalid: {}", s.id, problem_id, s.solver_name, e);
+            } else if v.score_changed(&s) {
+                num_changed += 1;
+                eprintln!("solution/{} (problem {}, {:?}): recorded cost={} dist={}, replayed cost={} dist={}",
+                    s.id, problem_id, s.solver_name, s.moves_cost, s.image_distance,
+                    v.moves_cost.unwrap(), v.image_distance.unwrap());
+            }
+            verifications.push(v);
+        }
+    }
+    eprintln!("verified {} solutions: {} invalid, {} with changed score", verifications.len(), num_invalid, num_changed);
+    if dry_run {
+        eprintln!("But not recording it, because it was a --dry-run!");
+    } else {
+        // Same connection, see db::Pool.
+        let mut tx = store.transaction();
+        for v in &verifications {
+            tx.record_verification(v);
+        }
+        tx.commit();
+    }
+}